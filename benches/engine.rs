@@ -0,0 +1,86 @@
+//! Benchmarks for the rules engine and the solver over the bundled levels.
+//! Run them with `cargo bench`. They don't open a window or need the network.
+//!
+//! The solver gives up after a few thousand positions, so each level takes up to about half a second.
+//! Only a handful of levels are solved by default. Set `SOKOBAN_BENCH_LEVELS` to a comma separated
+//! list of level numbers, or to `all`, to pick others.
+
+#[macro_use]
+extern crate criterion;
+extern crate sokoban_rs;
+
+use std::env;
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput};
+
+use sokoban_rs::{Direction, Game, Level, LEVELS, load_levels};
+use sokoban_rs::difficulty;
+
+/// The levels solved when `SOKOBAN_BENCH_LEVELS` isn't set: three the solver finishes and two it doesn't.
+const SOLVER_LEVELS: [usize; 5] = [1, 2, 3, 4, 15];
+
+/// Moves made in each run of the `make_move` benchmark.
+const MOVES: usize = 1_000;
+
+fn levels() -> Vec<Level> {
+    load_levels(LEVELS).expect("levels.txt should parse")
+}
+
+/// The level numbers to solve, counting from 1.
+fn solver_levels(count: usize) -> Vec<usize> {
+    match env::var("SOKOBAN_BENCH_LEVELS") {
+        Ok(ref list) if list == "all" => (1..count + 1).collect(),
+        Ok(list) => list.split(',')
+                        .filter_map(|n| n.trim().parse().ok())
+                        .filter(|&n| n >= 1 && n <= count)
+                        .collect(),
+        Err(_) => SOLVER_LEVELS.to_vec(),
+    }
+}
+
+fn bench_load_levels(c: &mut Criterion) {
+    let mut group = c.benchmark_group("load_levels");
+    group.throughput(Throughput::Bytes(LEVELS.len() as u64));
+    group.bench_function("levels.txt", |b| b.iter(|| load_levels(criterion::black_box(LEVELS)).unwrap()));
+    group.finish();
+}
+
+fn bench_make_move(c: &mut Criterion) {
+    let level = levels().remove(0);
+    // A fixed walk that bumps into walls and pushes stars around, so every branch gets its share
+    let pattern = [Direction::Up, Direction::Left, Direction::Left, Direction::Down, Direction::Right,
+                   Direction::Right, Direction::Right, Direction::Up, Direction::Down, Direction::Left];
+    let moves = pattern.iter().cycle().cloned().take(MOVES).collect::<Vec<_>>();
+    let mut group = c.benchmark_group("make_move");
+    group.throughput(Throughput::Elements(MOVES as u64));
+    group.bench_function("level 1", |b| {
+        b.iter_batched(|| Game::from_level(level.clone()), |mut game| {
+            for &dir in &moves {
+                game.make_move(dir);
+            }
+            game
+        }, BatchSize::SmallInput)
+    });
+    group.finish();
+}
+
+fn bench_solver(c: &mut Criterion) {
+    let levels = levels();
+    let mut group = c.benchmark_group("solve");
+    group.sample_size(10);
+    for number in solver_levels(levels.len()) {
+        let level = &levels[number - 1];
+        // Counting positions as elements makes criterion report nodes per second
+        let (nodes, solution) = difficulty::solve(level);
+        if nodes == 0 {
+            continue
+        }
+        group.throughput(Throughput::Elements(nodes as u64));
+        let name = if solution.is_some() { format!("level {}", number) } else { format!("level {} (unsolved)", number) };
+        group.bench_with_input(BenchmarkId::from_parameter(name), level, |b, level| b.iter(|| difficulty::solve(level)));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_load_levels, bench_make_move, bench_solver);
+criterion_main!(benches);
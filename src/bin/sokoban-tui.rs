@@ -0,0 +1,256 @@
+//! A text mode front end, for playing and debugging levels over SSH without a display.
+//! It draws the level in the level file format and doesn't need SDL.
+//!
+//! Usage: sokoban-tui [level file] [level number]
+//! It takes the same --verify and --generate options as the SDL game as well.
+//! Keys come from the same keys.txt as the SDL game, so keys remapped in its settings screen work here too.
+//! Actions the terminal has no use for, like the camera ones, are left out.
+
+extern crate crossterm;
+extern crate sokoban_rs;
+
+use std::env;
+use std::io;
+use std::io::prelude::*;
+use std::process;
+use std::time::Duration;
+
+use crossterm::{cursor, execute, queue, terminal};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::style::{Attribute, Color, Print, ResetColor, SetAttribute, SetForegroundColor};
+use crossterm::terminal::{ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+
+use sokoban_rs::*;
+use sokoban_rs::hold;
+use sokoban_rs::keys::{self, Action, Bindings};
+use sokoban_rs::transform::Transform;
+
+/// Lines above the map, for the level number and the counters.
+const HEADER_ROWS: u16 = 3;
+
+/// The name the key bindings know `code` by, which is the name SDL gives the same key.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Up => "Up",
+        KeyCode::Down => "Down",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Tab => "Tab",
+        KeyCode::Backspace => "Backspace",
+        KeyCode::Enter => "Return",
+        KeyCode::Esc => "Escape",
+        KeyCode::Delete => "Delete",
+        KeyCode::Home => "Home",
+        KeyCode::End => "End",
+        KeyCode::PageUp => "PageUp",
+        KeyCode::PageDown => "PageDown",
+        KeyCode::F(n) => return Some(format!("F{}", n)),
+        KeyCode::Char(' ') => "Space",
+        // Terminals don't tell the keypad apart, so digits count as the keypad, where the hexoban diagonals are
+        KeyCode::Char(c) if c.is_ascii_digit() => return Some(format!("Keypad {}", c)),
+        KeyCode::Char(c) => return Some(c.to_uppercase().collect()),
+        _ => return None,
+    };
+    Some(name.to_string())
+}
+
+/// The key to show for `action`. Gamepads don't reach the terminal, so it's the first key on the keyboard.
+fn key_label(bindings: &Bindings, action: Action) -> String {
+    bindings.keys(action).iter().find(|k| !k.starts_with("Pad ")).cloned().unwrap_or_else(|| "?".to_string())
+}
+
+/// The help line at the bottom of the screen, with the keys `bindings` has for each action.
+fn help(bindings: &Bindings) -> String {
+    let key = |action| key_label(bindings, action);
+    let keys = |actions: &[Action]| actions.iter().map(|&a| key(a)).collect::<Vec<_>>().join("/");
+    format!("{} move  {} hex diagonals  {} pusher  {} undo/redo  {} reset  {} level  {} mode  {} turn  {} quit",
+            keys(&[Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight]),
+            keys(&[Action::MoveUpLeft, Action::MoveUpRight, Action::MoveDownLeft, Action::MoveDownRight]),
+            key(Action::SwitchPusher), keys(&[Action::Undo, Action::Redo]), key(Action::Reset),
+            keys(&[Action::NextLevel, Action::PreviousLevel]), key(Action::NextMode),
+            keys(&[Action::TurnLevel, Action::MirrorLevel]), key(Action::Quit))
+}
+
+/// Puts the terminal into raw mode on an alternate screen, and puts it back when dropped, even after a panic.
+struct RawTerminal;
+impl RawTerminal {
+    fn new() -> io::Result<RawTerminal> {
+        terminal::enable_raw_mode()?;
+        execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(RawTerminal)
+    }
+}
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+fn new_game(levels: &[Level], level_number: i32, challenge: Challenge, view: Transform) -> Game {
+    Game::from_level(levels[level_number as usize].clone()).with_challenge(challenge).transformed(view)
+}
+
+/// The first row or column to draw so that `pos` stays in view when `size` cells don't fit into `room`.
+fn scroll(pos: usize, size: usize, room: usize) -> usize {
+    if size <= room {
+        0
+    } else {
+        pos.saturating_sub(room / 2).min(size - room)
+    }
+}
+
+fn tint(color: Option<u8>, uncolored: Color) -> Color {
+    match color {
+        Some(c) => {
+            let (r, g, b) = color_tint(c);
+            Color::Rgb { r, g, b }
+        },
+        None => uncolored,
+    }
+}
+
+/// The color a cell of `map_lines` is drawn in.
+fn cell_color(game: &Game, c: char, pos: Position) -> Color {
+    let state = &game.state;
+    match c {
+        '#' => Color::DarkGrey,
+        '$' | '*' => {
+            let star = state.stars.iter().find(|s| s.position == pos).and_then(|s| s.color);
+            tint(star, if c == '*' { Color::Green } else { Color::Yellow })
+        },
+        '.' => tint(state.goals.iter().find(|g| g.position == pos).and_then(|g| g.color), Color::Red),
+        '@' | '+' if state.player().position == pos => Color::Magenta,
+        '@' | '+' => Color::DarkMagenta,
+        '~' => Color::Cyan,
+        '^' | 'v' | '<' | '>' => Color::Blue,
+        'o' => Color::DarkRed,
+        _ => Color::Reset,
+    }
+}
+
+fn draw(out: &mut io::Stdout, game: &Game, level_number: i32, bindings: &Bindings, help: &str, message: Option<&str>) -> io::Result<()> {
+    let (cols, rows) = terminal::size()?;
+    let status = if let Some(message) = message {
+        message.to_string()
+    } else if game.solved() {
+        format!("Solved in {}! {} for the next level", game.result_text(), key_label(bindings, Action::NextLevel))
+    } else if game.failed() {
+        format!("{} {} to try again", game.failure_text(), key_label(bindings, Action::Reset))
+    } else {
+        game.status_text()
+    };
+    queue!(out, terminal::Clear(ClearType::All),
+           cursor::MoveTo(0, 0), SetAttribute(Attribute::Bold), Print(hud_text(game, level_number)), SetAttribute(Attribute::Reset),
+           cursor::MoveTo(0, 1), Print(status),
+           cursor::MoveTo(0, rows.saturating_sub(1)), Print(help.chars().take(cols as usize).collect::<String>()))?;
+
+    let level = &game.level;
+    let lines = level.map_lines(&game.state);
+    let room = (cols as usize, rows.saturating_sub(HEADER_ROWS + 1) as usize);
+    let player = game.state.player().position;
+    let left = scroll(player.x, level.width, room.0);
+    let top = scroll(player.y, level.height, room.1);
+    for (row, line) in lines.iter().enumerate().skip(top).take(room.1) {
+        queue!(out, cursor::MoveTo(0, HEADER_ROWS + (row - top) as u16))?;
+        for (x, c) in line.chars().enumerate().skip(left).take(room.0) {
+            queue!(out, SetForegroundColor(cell_color(game, c, Position::new(x, row))), Print(c))?;
+        }
+    }
+    queue!(out, ResetColor)?;
+    out.flush()
+}
+
+fn run(levels: &[Level], mut level_number: i32, bindings: &Bindings) -> io::Result<()> {
+    let mut out = io::stdout();
+    let help = help(bindings);
+    let mut challenge = Challenge::Free;
+    let mut view = Transform::new(0, false);
+    let mut game = new_game(levels, level_number, challenge, view);
+    let mut redraw = true;
+    // A message that takes the place of the status line until the next key
+    let mut message: Option<String> = None;
+    loop {
+        if redraw || game.challenge == Challenge::Countdown {
+            draw(&mut out, &game, level_number, bindings, &help, message.as_ref().map(|m| &m[..]))?;
+        }
+        // Wake up now and then so the countdown clock keeps ticking
+        redraw = event::poll(Duration::from_millis(250))?;
+        if !redraw {
+            continue
+        }
+        let key = match event::read()? {
+            Event::Key(key) if key.kind != KeyEventKind::Release => key,
+            _ => continue,
+        };
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return Ok(())
+        }
+        message = None;
+        let playing = !game.solved() && !game.failed();
+        let len = levels.len() as i32;
+        match key_name(key.code).and_then(|name| bindings.action(&name)) {
+            Some(Action::Quit) => return Ok(()),
+            Some(action) if playing && hold::is_move(action) => game.act(action),
+            Some(action @ Action::SwitchPusher) | Some(action @ Action::Undo) | Some(action @ Action::Redo) => game.act(action),
+            Some(Action::Reset) => game = new_game(levels, level_number, challenge, view),
+            Some(Action::NextLevel) => {
+                level_number = (level_number + len + 1) % len;
+                game = new_game(levels, level_number, challenge, view);
+            },
+            Some(Action::PreviousLevel) => {
+                level_number = (level_number + len - 1) % len;
+                game = new_game(levels, level_number, challenge, view);
+            },
+            Some(Action::NextMode) => {
+                challenge = challenge.next();
+                game = new_game(levels, level_number, challenge, view);
+            },
+            Some(action @ Action::TurnLevel) | Some(action @ Action::MirrorLevel) => {
+                let t = if action == Action::TurnLevel { Transform::new(1, false) } else { Transform::new(0, true) };
+                // Hexoban levels can't be turned, and the next levels shouldn't come up turned because of it
+                match t.level(&game.level) {
+                    Ok(_) => {
+                        view = view.then(t);
+                        game = game.transformed(t);
+                    },
+                    Err(e) => message = Some(e),
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(code) = cli::run(&args) {
+        process::exit(code)
+    }
+    let levels = match args.get(1) {
+        Some(path) => load_level_file(path),
+        None => default_levels(),
+    };
+    let levels = levels.unwrap_or_else(|e| {
+        eprintln!("Could not load the levels: {}", e);
+        process::exit(2)
+    });
+    if levels.is_empty() {
+        eprintln!("There are no levels to play");
+        process::exit(2)
+    }
+    let level_number = match args.get(2).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) if n >= 1 && n <= levels.len() => n as i32 - 1,
+        Some(_) => {
+            eprintln!("The level number has to be between 1 and {}", levels.len());
+            process::exit(2)
+        },
+        None => 0,
+    };
+    let bindings = Bindings::load(keys::default_path());
+    let result = RawTerminal::new().and_then(|_terminal| run(&levels, level_number, &bindings));
+    if let Err(e) = result {
+        eprintln!("Terminal error: {}", e);
+        process::exit(1)
+    }
+}
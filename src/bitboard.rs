@@ -0,0 +1,175 @@
+//! A compact form of `GameState` for searches that look at millions of positions: stars are
+//! kept as bitsets over the squares of the level and the whole position has a Zobrist hash.
+
+use std::hash::{Hash, Hasher};
+use std::iter;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use {Direction, GameState, Level, Player, Position, Star};
+use reachability::Reachability;
+
+/// A set of squares, one bit each.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Bitset {
+    words: Vec<u64>,
+}
+impl Bitset {
+    pub fn new(size: usize) -> Bitset {
+        Bitset { words: vec![0; size.div_ceil(64)] }
+    }
+    pub fn contains(&self, i: usize) -> bool {
+        self.words.get(i / 64).is_some_and(|w| w & (1 << (i % 64)) != 0)
+    }
+    pub fn insert(&mut self, i: usize) {
+        self.words[i / 64] |= 1 << (i % 64);
+    }
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|w| w.count_ones() as usize).sum()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&w| w == 0)
+    }
+    /// Whether any square is in both sets.
+    pub fn intersects(&self, other: &Bitset) -> bool {
+        self.words.iter().zip(&other.words).any(|(a, b)| a & b != 0)
+    }
+    /// The squares in the set, in order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.words.iter().enumerate().flat_map(|(i, &word)| {
+            let mut word = word;
+            iter::from_fn(move || {
+                if word == 0 {
+                    return None
+                }
+                let square = i * 64 + word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(square)
+            })
+        })
+    }
+}
+
+/// A position in a search. Two states are equal if they have the same stars, filled holes
+/// and pusher square. Hashing only feeds in the Zobrist hash, so it's cheap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactState {
+    /// The squares of the stars, one set for each of the board's star colors
+    pub stars: Vec<Bitset>,
+    pub filled_holes: Bitset,
+    /// The square of the active pusher, or the first square it can walk to if it was normalized
+    pub player: usize,
+    pub hash: u64,
+}
+impl Hash for CompactState {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash)
+    }
+}
+
+/// What's needed to turn states of one single pusher level into compact ones and back.
+#[derive(Debug, Clone)]
+pub struct Board {
+    width: usize,
+    size: usize,
+    /// The star colors in the level, in the order of `CompactState::stars`
+    colors: Vec<Option<u8>>,
+    /// The start of the level, which supplies the goals when states are expanded
+    start: GameState,
+    /// Random numbers for every star color on every square, and for holes and pushers
+    zobrist_stars: Vec<Vec<u64>>,
+    zobrist_holes: Vec<u64>,
+    zobrist_player: Vec<u64>,
+}
+impl Board {
+    pub fn new(level: &Level) -> Board {
+        let size = level.width * level.height;
+        let mut colors = level.start_state.stars.iter().map(|s| s.color).collect::<Vec<_>>();
+        colors.sort();
+        colors.dedup();
+        // A fixed seed, so hashes are the same every run
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        let mut table = || (0..size).map(|_| rng.gen()).collect::<Vec<u64>>();
+        Board {
+            width: level.width,
+            size,
+            zobrist_stars: colors.iter().map(|_| table()).collect(),
+            zobrist_holes: table(),
+            zobrist_player: table(),
+            colors,
+            start: level.start_state.clone(),
+        }
+    }
+    pub fn square(&self, pos: Position) -> usize {
+        pos.y * self.width + pos.x
+    }
+    pub fn position(&self, square: usize) -> Position {
+        Position::new(square % self.width, square / self.width)
+    }
+    /// Whether every pusher, star and hole in `state` is on the board.
+    pub fn fits(&self, state: &GameState) -> bool {
+        state.players.iter().all(|p| self.contains(p.position)) && state.stars.iter().all(|s| self.contains(s.position))
+            && state.filled_holes.iter().all(|&h| self.contains(h))
+    }
+    /// A set of squares that fits this board.
+    pub fn bitset(&self) -> Bitset {
+        Bitset::new(self.size)
+    }
+    /// `state` with the active pusher on the square it stands on.
+    pub fn compact(&self, state: &GameState) -> CompactState {
+        self.compact_with_player(state, state.player().position)
+    }
+    /// `state` with the active pusher moved to the first square it can walk to, so every
+    /// position it can walk between comes out the same. Only use it on levels without special floors,
+    /// where every walk can be walked back.
+    pub fn normalized(&self, state: &GameState, reach: &Reachability) -> CompactState {
+        self.compact_with_player(state, reach.normalized())
+    }
+    fn compact_with_player(&self, state: &GameState, player: Position) -> CompactState {
+        let player = self.square(player);
+        let mut hash = self.zobrist_player[player];
+        let mut stars = vec![self.bitset(); self.colors.len()];
+        for star in &state.stars {
+            let color = self.colors.binary_search(&star.color).unwrap();
+            let square = self.square(star.position);
+            stars[color].insert(square);
+            hash ^= self.zobrist_stars[color][square];
+        }
+        let mut filled_holes = self.bitset();
+        for &hole in &state.filled_holes {
+            let square = self.square(hole);
+            filled_holes.insert(square);
+            hash ^= self.zobrist_holes[square];
+        }
+        CompactState { stars, filled_holes, player, hash }
+    }
+    /// Turns `compact` back into a state with no moves made, the pusher facing right.
+    pub fn expand(&self, compact: &CompactState) -> GameState {
+        let mut stars = Vec::new();
+        for (set, &color) in compact.stars.iter().zip(&self.colors) {
+            stars.extend(set.iter().map(|square| Star { position: self.position(square), color }));
+        }
+        GameState {
+            players: vec![Player::new(self.position(compact.player), Direction::Right)],
+            active: 0,
+            stars,
+            goals: self.start.goals.clone(),
+            filled_holes: compact.filled_holes.iter().map(|square| self.position(square)).collect(),
+            steps: 0,
+            pushes: 0,
+        }
+    }
+    /// Whether `pos` is a square of the board. Stepping off an open edge of a level can give
+    /// positions that aren't, wrapped around to huge numbers.
+    pub fn contains(&self, pos: Position) -> bool {
+        pos.x < self.width && pos.y < self.size / self.width
+    }
+    pub fn is_star(&self, compact: &CompactState, pos: Position) -> bool {
+        self.contains(pos) && compact.stars.iter().any(|set| set.contains(self.square(pos)))
+    }
+    /// Whether any star stands on one of `squares`.
+    pub fn any_star_on(&self, compact: &CompactState, squares: &Bitset) -> bool {
+        compact.stars.iter().any(|set| set.intersects(squares))
+    }
+}
@@ -0,0 +1,141 @@
+//! A canonical form for levels, so the same puzzle can be recognised whichever pack it came from
+//! and however it was drawn.
+
+use std::collections::HashMap;
+
+use {Level, Position, Tile, Topology};
+use transform::Transform;
+
+/// `level` without anything that doesn't change the puzzle: walls the pushers can never touch are
+/// removed and the map is trimmed down to what's left.
+pub fn normalized(level: &Level) -> Level {
+    let state = &level.start_state;
+    let offsets = level.topology.offsets();
+    let inside = |x: i32, y: i32| {
+        let pos = Position::new(x as usize, y as usize);
+        match level.tile(x, y) {
+            Some(Tile::Wall) | None => false,
+            Some(Tile::OutsideFloor) => state.stars.iter().any(|s| s.position == pos)
+                                        || state.goals.iter().any(|g| g.position == pos),
+            Some(_) => true,
+        }
+    };
+    let mut keep = vec![vec![false; level.width]; level.height];
+    for (y, row) in keep.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            let (x, y) = (x as i32, y as i32);
+            *cell = inside(x, y) || level.tile(x, y) == Some(Tile::Wall)
+                                    && offsets.iter().any(|&(dx, dy)| inside(x + dx, y + dy));
+        }
+    }
+    let kept = || keep.iter().enumerate().flat_map(|(y, row)| row.iter().enumerate().filter(|c| *c.1).map(move |(x, _)| (x, y)));
+    let min_x = kept().map(|c| c.0).min().unwrap_or(0);
+    let max_x = kept().map(|c| c.0).max().unwrap_or(0);
+    let min_y = kept().map(|c| c.1).min().unwrap_or(0);
+    let max_y = kept().map(|c| c.1).max().unwrap_or(0);
+
+    let map = (min_y..max_y + 1).map(|y| {
+        (min_x..max_x + 1).map(|x| if keep[y][x] { level.map[y][x] } else { Tile::OutsideFloor }).collect()
+    }).collect();
+    let shift = |p: Position| Position::new(p.x - min_x, p.y - min_y);
+    let mut start_state = state.clone();
+    for player in &mut start_state.players {
+        player.position = shift(player.position);
+    }
+    for star in &mut start_state.stars {
+        star.position = shift(star.position);
+    }
+    for goal in &mut start_state.goals {
+        goal.position = shift(goal.position);
+    }
+    for hole in &mut start_state.filled_holes {
+        *hole = shift(*hole);
+    }
+    Level {
+        width: max_x - min_x + 1,
+        height: max_y - min_y + 1,
+        topology: level.topology,
+        map,
+        start_state,
+        metadata: level.metadata.clone(),
+    }
+}
+
+/// Writes out the start of a level with teleporters and colors numbered in the order they're
+/// first seen, so levels that only differ in how those are numbered come out the same.
+fn describe(level: &Level) -> String {
+    let mut teleporters = HashMap::new();
+    let mut lines = level.map_lines(&level.start_state)
+                         .into_iter()
+                         .map(|line| line.chars().map(|c| match c.to_digit(10) {
+                             Some(d) => {
+                                 let next = teleporters.len() as u32;
+                                 std::char::from_digit(*teleporters.entry(d).or_insert(next), 10).unwrap()
+                             },
+                             None => c,
+                         }).collect::<String>())
+                         .collect::<Vec<_>>();
+
+    let mut stars = level.start_state.stars.iter().map(|s| (s.position.y, s.position.x, s.color)).collect::<Vec<_>>();
+    let mut goals = level.start_state.goals.iter().map(|g| (g.position.y, g.position.x, g.color)).collect::<Vec<_>>();
+    if stars.iter().chain(&goals).any(|s| s.2.is_some()) {
+        stars.sort();
+        goals.sort();
+        let mut colors = HashMap::new();
+        let mut list = |items: &[(usize, usize, Option<u8>)]| {
+            items.iter().map(|&(_, _, color)| match color {
+                Some(c) => {
+                    let next = colors.len();
+                    colors.entry(c).or_insert(next).to_string()
+                },
+                None => "-".to_string(),
+            }).collect::<Vec<_>>().join(" ")
+        };
+        let star_colors = list(&stars);
+        let goal_colors = list(&goals);
+        lines.insert(0, format!("Goal colors: {}", goal_colors));
+        lines.insert(0, format!("Star colors: {}", star_colors));
+    }
+    if level.topology == Topology::Hex {
+        lines.insert(0, "Topology: Hexoban".to_string());
+    }
+    lines.join("\n")
+}
+
+/// The way `level` is turned to get its canonical text: whichever way gives the text that sorts first.
+/// Hexoban levels are never turned.
+pub fn transform(level: &Level) -> Transform {
+    canonical(&normalized(level)).0
+}
+
+/// The canonical text of a level: its normalized form, turned the way `transform` picks.
+pub fn canonical_text(level: &Level) -> String {
+    canonical(&normalized(level)).1
+}
+
+fn canonical(level: &Level) -> (Transform, String) {
+    match level.topology {
+        Topology::Square => Transform::all().into_iter()
+                                            .map(|t| (t, describe(&t.level(level).unwrap())))
+                                            .min_by(|a, b| a.1.cmp(&b.1))
+                                            .unwrap(),
+        Topology::Hex => (Transform::new(0, false), describe(level)),
+    }
+}
+
+/// A hash of the canonical text, which stays the same between runs and versions of Rust.
+pub fn hash(level: &Level) -> u64 {
+    // 64 bit FNV-1a
+    canonical_text(level).bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// A solution to `level` turned to fit its canonical form, so it can be kept under the hash
+/// and played on any copy of the level, however that copy is turned.
+pub fn to_canonical(level: &Level, solution: &str) -> String {
+    transform(level).lurd(solution)
+}
+
+/// A solution kept for the canonical form, turned back to fit `level`.
+pub fn from_canonical(level: &Level, solution: &str) -> String {
+    transform(level).inverse().lurd(solution)
+}
@@ -0,0 +1,91 @@
+//! The batch modes both front ends share, for checking and making levels from the command line.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use {load_level_file, difficulty, generator, verifier};
+
+/// Checks the solutions in a level file and prints a line for every level.
+/// Returns whether all of them solve their level.
+pub fn verify_file(path: &str) -> Result<bool, String> {
+    let levels = load_level_file(path)?;
+    let mut all_ok = true;
+    for (i, result) in verifier::verify_levels(&levels).into_iter().enumerate() {
+        match result {
+            Some(v) if v.solved => println!("Level {}: solved in {} moves, {} pushes", i+1, v.moves, v.pushes),
+            Some(v) => {
+                all_ok = false;
+                match v.first_illegal {
+                    Some(index) => println!("Level {}: illegal move at index {} after {} moves, {} pushes", i+1, index, v.moves, v.pushes),
+                    None => println!("Level {}: not solved after {} moves, {} pushes", i+1, v.moves, v.pushes),
+                }
+            },
+            None => println!("Level {}: no solution", i+1),
+        }
+    }
+    Ok(all_ok)
+}
+
+/// A seed for the level generator that's different every time.
+pub fn clock_seed() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() ^ d.subsec_nanos() as u64).unwrap_or(0)
+}
+
+/// Prints generated levels in the level file format. `options` are the arguments after --generate:
+/// --seed N, --count N, --stars N, --size WxH (in templates), --difficulty N (in pushes) and --attempts N.
+pub fn generate_levels(options: &[String]) -> Result<(), String> {
+    let mut settings = generator::DEFAULT_SETTINGS;
+    let mut seed = clock_seed();
+    let mut count = 1;
+    for pair in options.chunks(2) {
+        let value = pair.get(1).ok_or_else(|| format!("{} needs a value", pair[0]))?;
+        let number = || value.parse::<usize>().map_err(|_| format!("'{}' is not a number", value));
+        match pair[0].as_str() {
+            "--seed" => seed = number()? as u64,
+            "--count" => count = number()?,
+            "--stars" => settings.stars = number()?,
+            "--difficulty" => settings.difficulty = number()?,
+            "--attempts" => settings.attempts = number()?,
+            "--size" => {
+                let (w, h) = value.split_at(value.find('x').ok_or_else(|| format!("'{}' is not a size like 3x2", value))?);
+                settings.width = w.parse().map_err(|_| format!("'{}' is not a size like 3x2", value))?;
+                settings.height = h[1..].parse().map_err(|_| format!("'{}' is not a size like 3x2", value))?;
+            },
+            option => return Err(format!("Unknown option {}", option)),
+        }
+    }
+    for i in 0..count as u64 {
+        // A seed near the top wraps around instead of overflowing
+        let seed = seed.wrapping_add(i);
+        let mut level = generator::generate(settings, seed)?;
+        let score = difficulty::rate(&level).score;
+        level.set_metadata("Difficulty", score.to_string());
+        println!("; Generated with --seed {}, {} pushes", seed, level.par().map_or(0, |par| par.1));
+        println!("{}\n", level.text());
+    }
+    Ok(())
+}
+
+/// Runs the batch mode `args` ask for, if any, and gives the exit code for the process.
+/// Gives None when the game should be played instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    if args.len() == 3 && args[1] == "--verify" {
+        // Batch mode: check the solutions in a level file instead of playing
+        match verify_file(&args[2]) {
+            Ok(true) => return Some(0),
+            Ok(false) => return Some(1),
+            Err(e) => {
+                eprintln!("Could not verify {}: {}", args[2], e);
+                return Some(2)
+            },
+        }
+    }
+    if args.len() >= 2 && args[1] == "--generate" {
+        // Batch mode: print new levels instead of playing
+        if let Err(e) = generate_levels(&args[2..]) {
+            eprintln!("Could not generate levels: {}", e);
+            return Some(2)
+        }
+        return Some(0)
+    }
+    None
+}
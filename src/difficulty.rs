@@ -0,0 +1,203 @@
+//! Estimates how hard a level is, so levels can be sorted and recommended.
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use {Game, GameState, Level, Position, Tile, floodfill};
+use bitboard::Board;
+use reachability::Reachability;
+
+/// The solver gives up after looking at this many positions.
+const MAX_NODES: usize = 20_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Difficulty {
+    pub stars: usize,
+    /// Moves and pushes of the level's "Solution", or of the solver's if the level has none
+    pub solution: Option<(usize, usize)>,
+    /// Positions the solver looked at before it solved the level, or None if it gave up
+    pub nodes: Option<usize>,
+    /// Floor squares a star can never be pushed from onto a goal
+    pub dead_squares: usize,
+    /// Pieces of floor the pusher is shut out of by stars at the start
+    pub corrals: usize,
+    /// All of the above rolled into one number. Higher is harder.
+    pub score: usize,
+}
+
+pub fn rate(level: &Level) -> Difficulty {
+    let dead = dead_squares(level);
+    let (searched, solved_in) = search(level, &dead);
+    let nodes = solved_in.map(|_| searched);
+    let solution = level.par().or(solved_in);
+    let dead_squares = dead.iter().map(|row| row.iter().filter(|&&d| d).count()).sum();
+    let corrals = corrals(level);
+    let stars = level.start_state.stars.len();
+    // The solver's effort grows exponentially with how tricky a level is, so it counts by its log.
+    // The weights are rough, and only meant to put levels in a sensible order.
+    let effort = match nodes {
+        Some(n) => (n as f64).log2() as usize,
+        None => (MAX_NODES as f64).log2() as usize + 4,
+    };
+    let pushes = solution.map_or(0, |s| s.1);
+    Difficulty {
+        stars,
+        solution,
+        nodes,
+        dead_squares,
+        corrals,
+        score: 3 * stars + pushes / 2 + 2 * effort + dead_squares / 4 + 3 * corrals,
+    }
+}
+
+/// Rates `levels` on another thread, sending back each level's index with its rating as it's done.
+/// Dropping the receiver stops it.
+pub fn spawn(levels: Vec<(usize, Level)>) -> Receiver<(usize, Difficulty)> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for (i, level) in levels {
+            if sender.send((i, rate(&level))).is_err() {
+                break
+            }
+        }
+    });
+    receiver
+}
+
+fn is_floor(level: &Level, x: i32, y: i32) -> bool {
+    match level.tile(x, y) {
+        Some(Tile::Wall) | Some(Tile::OutsideFloor) | None => false,
+        Some(_) => true,
+    }
+}
+
+/// Marks the floor squares that no goal can be reached from, found by pulling a star away
+/// from every goal in every way it can go. Colors and special floors are ignored.
+fn dead_squares(level: &Level) -> Vec<Vec<bool>> {
+    let offsets = level.topology.offsets();
+    let mut live = vec![vec![false; level.width]; level.height];
+    let mut queue = VecDeque::new();
+    for goal in &level.start_state.goals {
+        live[goal.position.y][goal.position.x] = true;
+        queue.push_back(goal.position);
+    }
+    while let Some(pos) = queue.pop_front() {
+        let (x, y) = (pos.x as i32, pos.y as i32);
+        for &(dx, dy) in &offsets {
+            // Pulling the star one step needs room for the pusher behind it as well
+            let (nx, ny) = (x + dx, y + dy);
+            if is_floor(level, nx, ny) && is_floor(level, nx + dx, ny + dy) && !live[ny as usize][nx as usize] {
+                live[ny as usize][nx as usize] = true;
+                queue.push_back(Position::new(nx as usize, ny as usize));
+            }
+        }
+    }
+    (0..level.height).map(|y| {
+        (0..level.width).map(|x| is_floor(level, x as i32, y as i32) && !live[y][x]).collect()
+    }).collect()
+}
+
+/// Counts the pieces of floor the pusher can't get to at the start because stars are in the way.
+fn corrals(level: &Level) -> usize {
+    let state = &level.start_state;
+    let offsets = level.topology.offsets();
+    let mut map = (0..level.height).map(|y| {
+        (0..level.width).map(|x| {
+            let open = is_floor(level, x as i32, y as i32)
+                       && !state.stars.iter().any(|s| s.position == Position::new(x, y));
+            if open { 1 } else { 0 }
+        }).collect::<Vec<u8>>()
+    }).collect::<Vec<_>>();
+    for active in 0..state.players.len() {
+        let reach = Reachability::new(level, &GameState { active, ..state.clone() });
+        for pos in reach.positions() {
+            map[pos.y][pos.x] = 2;
+        }
+    }
+    let mut count = 0;
+    for y in 0..level.height {
+        for x in 0..level.width {
+            if map[y][x] == 1 {
+                floodfill(&mut map, 1, 3, x, y, &offsets);
+                count += 1;
+            }
+        }
+    }
+    count
+}
+
+/// Solves the level one push at a time, walking the pusher to wherever it can push from,
+/// and gives up after `MAX_NODES` positions.
+/// Gives the number of positions it looked at and the moves and pushes of the solution, if it found one.
+/// Multiban levels aren't solved.
+pub fn solve(level: &Level) -> (usize, Option<(usize, usize)>) {
+    search(level, &dead_squares(level))
+}
+
+fn search(level: &Level, dead: &[Vec<bool>]) -> (usize, Option<(usize, usize)>) {
+    if level.start_state.players.len() != 1 {
+        return (0, None)
+    }
+    let board = Board::new(level);
+    // On plain floor every walk can be walked back, so positions can be normalized to
+    // where the pusher can walk. Dead squares only hold there as well.
+    let plain = !level.has_special_floors();
+    let compact = |state: &GameState| {
+        if plain {
+            board.normalized(state, &Reachability::new(level, state))
+        } else {
+            board.compact(state)
+        }
+    };
+    let mut dead_set = board.bitset();
+    for (y, row) in dead.iter().enumerate() {
+        for (x, _) in row.iter().enumerate().filter(|c| *c.1) {
+            dead_set.insert(board.square(Position::new(x, y)));
+        }
+    }
+
+    let mut game = Game::from_level(level.clone());
+    let start = compact(&game.state);
+    let mut seen = HashSet::new();
+    seen.insert(start.clone());
+    // Each position goes with where the pusher really stands, since a normalized position
+    // only says where it could walk to, and moves are counted from the real square
+    let mut queue = VecDeque::new();
+    queue.push_back((start, game.state.player().position, 0, 0));
+    while let Some((position, pusher, steps, pushes)) = queue.pop_front() {
+        let mut state = GameState { steps, pushes, ..board.expand(&position) };
+        state.players[0].position = pusher;
+        game.state = state.clone();
+        if game.solved() {
+            return (seen.len(), Some((steps, pushes)))
+        }
+        if seen.len() >= MAX_NODES {
+            break
+        }
+        let reach = Reachability::new(level, &state);
+        for pos in reach.positions() {
+            for &dir in level.topology.directions() {
+                if !board.is_star(&position, pos.move_in_direction(level.topology, dir)) {
+                    continue
+                }
+                game.state = state.clone();
+                game.state.players[0].position = pos;
+                game.state.steps += reach.distance(pos).unwrap();
+                game.make_move(dir);
+                let moved = &game.state;
+                if moved.pushes == pushes || !board.fits(moved) {
+                    continue
+                }
+                let next = compact(moved);
+                if plain && board.any_star_on(&next, &dead_set) {
+                    continue
+                }
+                if seen.insert(next.clone()) {
+                    queue.push_back((next, moved.player().position, moved.steps, moved.pushes));
+                }
+            }
+        }
+    }
+    (seen.len(), None)
+}
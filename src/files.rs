@@ -0,0 +1,136 @@
+//! Finding level files on disk for the in-game file browser, and remembering the ones opened lately.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use save;
+
+/// The extensions level files usually have.
+pub const LEVEL_EXTENSIONS: [&str; 3] = ["txt", "sok", "xsb"];
+
+/// How many recent files are remembered.
+pub const MAX_RECENT: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    /// The directory above the one being listed
+    Parent,
+    Directory,
+    LevelFile,
+}
+
+/// A line in the file browser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub kind: EntryKind,
+}
+impl Entry {
+    /// The name the browser shows, with a slash after directories.
+    pub fn name(&self) -> String {
+        let name = self.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        match self.kind {
+            EntryKind::Parent => "../".to_string(),
+            EntryKind::Directory => format!("{}/", name),
+            EntryKind::LevelFile => name,
+        }
+    }
+}
+
+pub fn is_level_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| LEVEL_EXTENSIONS.iter().any(|l| e.eq_ignore_ascii_case(l)))
+}
+
+/// The directories and level files in `dir`, directories first and each sorted by name.
+/// Hidden files are left out.
+pub fn list_dir(dir: &Path) -> Result<Vec<Entry>, String> {
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|e| format!("{}: {}", dir.display(), e))? {
+        let path = match entry {
+            Ok(entry) => entry.path(),
+            Err(_) => continue,
+        };
+        if path.file_name().is_none_or(|n| n.to_string_lossy().starts_with('.')) {
+            continue
+        }
+        if path.is_dir() {
+            dirs.push(path);
+        } else if is_level_file(&path) {
+            files.push(path);
+        }
+    }
+    dirs.sort();
+    files.sort();
+    let mut entries = Vec::new();
+    if let Some(parent) = dir.parent() {
+        entries.push(Entry { path: parent.to_path_buf(), kind: EntryKind::Parent });
+    }
+    entries.extend(dirs.into_iter().map(|path| Entry { path, kind: EntryKind::Directory }));
+    entries.extend(files.into_iter().map(|path| Entry { path, kind: EntryKind::LevelFile }));
+    Ok(entries)
+}
+
+/// The entries whose name holds `query`, ignoring case. The way up is only kept when there's no query.
+pub fn filter<'a>(entries: &'a [Entry], query: &str) -> Vec<&'a Entry> {
+    let query = query.to_lowercase();
+    entries.iter().filter(|e| {
+        if e.kind == EntryKind::Parent {
+            query.is_empty()
+        } else {
+            e.name().to_lowercase().contains(&query)
+        }
+    }).collect()
+}
+
+/// The level files opened lately, newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentFiles {
+    path: PathBuf,
+    pub files: Vec<PathBuf>,
+}
+impl RecentFiles {
+    /// Loads the list kept at `path`. A missing file just means nothing has been opened yet.
+    pub fn load(path: PathBuf) -> RecentFiles {
+        let files = match File::open(&path) {
+            Ok(f) => BufReader::new(f).lines().map_while(Result::ok)
+                         .filter(|line| !line.trim().is_empty())
+                         .map(PathBuf::from)
+                         .take(MAX_RECENT)
+                         .collect(),
+            Err(_) => Vec::new(),
+        };
+        RecentFiles { path, files }
+    }
+    /// Puts `file` at the top of the list.
+    pub fn add(&mut self, file: &Path) {
+        let file = file.canonicalize().unwrap_or_else(|_| file.to_path_buf());
+        self.files.retain(|f| *f != file);
+        self.files.insert(0, file);
+        self.files.truncate(MAX_RECENT);
+    }
+    /// The recent files as browser entries, leaving out the ones that have gone away.
+    pub fn entries(&self) -> Vec<Entry> {
+        self.files.iter()
+            .filter(|f| f.is_file())
+            .map(|f| Entry { path: f.clone(), kind: EntryKind::LevelFile })
+            .collect()
+    }
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut f = File::create(&self.path)?;
+        for file in &self.files {
+            writeln!(f, "{}", file.display())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn default_recent_path() -> PathBuf {
+    save::data_dir().join("recent.txt")
+}
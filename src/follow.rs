@@ -0,0 +1,92 @@
+//! Scrolling the camera after the pusher, so it can't walk off the screen on big levels.
+//! The pusher can wander around the middle of the screen, the dead zone, without the camera moving;
+//! once it steps out, the camera glides over until it's back inside.
+
+use std::time::Duration;
+
+use hold::MAX_FRAME;
+use {Game, Position, HALF_HEIGHT, HALF_WIDTH, TILE_HEIGHT, TILE_WIDTH};
+
+/// How far from the middle of the screen, in pixels, the pusher can go before the camera follows.
+pub const DEAD_ZONE: (i32, i32) = (HALF_WIDTH as i32 / 2, HALF_HEIGHT as i32 / 2);
+
+/// How quickly the camera catches up: the share of the way left it covers in a second is `1 - e^-FOLLOW_RATE`.
+pub const FOLLOW_RATE: f64 = 8.0;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Follow {
+    /// Whether the camera follows the pusher as it walks
+    pub on: bool,
+    /// Whether the camera is on its way to put the pusher right in the middle
+    centering: bool,
+    /// Whether the camera is on its way to bring the pusher back into the dead zone
+    chasing: bool,
+    /// Where the pusher was last frame. The camera only starts following when the pusher moves
+    /// or a level starts, so it can still be panned around freely in between.
+    last: Option<Position>,
+}
+impl Follow {
+    pub fn new(on: bool) -> Follow {
+        Follow { on, ..Follow::default() }
+    }
+    /// Turns following on or off. Turning it on brings the pusher into the dead zone straight away.
+    pub fn toggle(&mut self) {
+        self.on = !self.on;
+        self.chasing = self.on;
+    }
+    /// Scrolls back to the pusher, whether following or not.
+    pub fn center(&mut self) {
+        self.centering = true;
+    }
+    /// Moves the game's camera toward the pusher, by the time since the last frame.
+    pub fn update(&mut self, game: &mut Game, elapsed: Duration) {
+        let position = game.state.player().position;
+        if self.on && self.last != Some(position) {
+            self.chasing = true;
+        }
+        self.last = Some(position);
+        let camera = (game.camera.x_offset, game.camera.y_offset);
+        let centered = centered(game);
+        let target = if self.centering {
+            centered
+        } else if self.chasing && self.on {
+            into_dead_zone(camera, centered)
+        } else {
+            return
+        };
+        game.camera.pan(ease(camera.0, target.0, elapsed), ease(camera.1, target.1, elapsed));
+        let camera = (game.camera.x_offset, game.camera.y_offset);
+        if camera == target {
+            self.centering = false;
+            self.chasing = false;
+        }
+    }
+}
+
+/// The camera offsets that put the active pusher in the middle of the screen, or as near as the camera can pan.
+pub fn centered(game: &Game) -> (i32, i32) {
+    let level = &game.level;
+    let Position { x, y } = game.state.player().position;
+    let (left, top) = level.topology.tile_origin(x, y);
+    let (w, h) = level.topology.surface_size(level.width, level.height);
+    // The level is drawn around the middle of the screen, moved by the camera's offsets
+    let x = w as i32 / 2 - left - TILE_WIDTH as i32 / 2;
+    let y = h as i32 / 2 - top - TILE_HEIGHT as i32 / 2;
+    let camera = &game.camera;
+    (x.max(-camera.max_x_pan).min(camera.max_x_pan), y.max(-camera.max_y_pan).min(camera.max_y_pan))
+}
+
+/// The nearest offsets to `camera` that have the pusher in the dead zone, given the offsets that center it.
+fn into_dead_zone(camera: (i32, i32), centered: (i32, i32)) -> (i32, i32) {
+    let (dx, dy) = DEAD_ZONE;
+    (camera.0.max(centered.0 - dx).min(centered.0 + dx), camera.1.max(centered.1 - dy).min(centered.1 + dy))
+}
+
+/// How far to move from `from` toward `to` this frame. It slows down as it gets close, but always moves a pixel
+/// until it's there.
+fn ease(from: i32, to: i32, elapsed: Duration) -> i32 {
+    let left = to - from;
+    let share = 1.0 - (-FOLLOW_RATE * elapsed.min(MAX_FRAME).as_secs_f64()).exp();
+    let step = (left as f64 * share).round() as i32;
+    if step == 0 { left.signum() } else { step }
+}
@@ -0,0 +1,180 @@
+//! Makes new levels by building a room out of small templates, putting the stars on their goals
+//! and pulling them away again. Every pull can be undone by a push, so every level it makes can be solved.
+
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
+
+use {Level, Position, Topology, floodfill};
+use optimizer::{self, Metric, Vicinity};
+use transform::Transform;
+
+/// Rooms are put together from these 3x3 pieces, each one turned and mirrored at random.
+const TEMPLATES: [[&str; 3]; 11] = [
+    ["   ", "   ", "   "],
+    ["#  ", "   ", "   "],
+    ["## ", "   ", "   "],
+    ["###", "   ", "   "],
+    ["## ", "#  ", "   "],
+    ["#  ", "#  ", "#  "],
+    ["   ", " # ", "   "],
+    ["#  ", "   ", "  #"],
+    ["   ", " ##", "   "],
+    ["## ", "## ", "   "],
+    ["# #", "   ", "   "],
+];
+/// The vicinity used to shorten the pulled solutions. It's kept small, since it runs on every attempt.
+const VICINITY: Vicinity = Vicinity { stars: 1, radius: 3 };
+
+/// The longest backwards walk taken to make one level.
+const MAX_STEPS: usize = 2_000;
+
+/// What kind of level to make.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    /// The size of the room in templates, not counting the wall around it
+    pub width: usize,
+    pub height: usize,
+    pub stars: usize,
+    /// The number of pushes the level should take to solve
+    pub difficulty: usize,
+    /// How many rooms to try before settling for the closest one
+    pub attempts: usize,
+}
+pub const DEFAULT_SETTINGS: Settings = Settings { width: 3, height: 3, stars: 3, difficulty: 15, attempts: 20 };
+
+/// Makes a level on another thread, sending it back when it's done.
+pub fn spawn(settings: Settings, seed: u64) -> Receiver<Result<Level, String>> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        // Nobody is listening anymore if this fails, which is fine
+        let _ = sender.send(generate(settings, seed));
+    });
+    receiver
+}
+
+/// Makes a level as close to `settings.difficulty` as it can. The same seed always gives the same level.
+/// The level's "Solution" is the shortest one the optimizer found, and its difficulty is the pushes that takes.
+pub fn generate(settings: Settings, seed: u64) -> Result<Level, String> {
+    if settings.width == 0 || settings.height == 0 || settings.stars == 0 {
+        return Err("Levels need at least one template and one star".to_string());
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut best: Option<(usize, Level)> = None;
+    for _ in 0..settings.attempts {
+        let level = match attempt(&mut rng, settings) {
+            Some(level) => level,
+            None => continue,
+        };
+        let pushes = level.par().map_or(0, |par| par.1);
+        let off = pushes.abs_diff(settings.difficulty);
+        if best.as_ref().is_none_or(|b| off < b.0) {
+            best = Some((off, level));
+        }
+        if off == 0 {
+            break
+        }
+    }
+    best.map(|b| b.1).ok_or_else(|| format!("Could not fit {} stars into a {}x{} room", settings.stars, settings.width, settings.height))
+}
+
+/// Tries to make one level, giving up if the room or the pulls don't work out.
+fn attempt(rng: &mut StdRng, settings: Settings) -> Option<Level> {
+    let floor = room(rng, settings.width, settings.height)?;
+    let cells = floor.iter()
+                     .enumerate()
+                     .flat_map(|(y, row)| row.iter().enumerate().filter(|c| *c.1).map(move |(x, _)| Position::new(x, y)))
+                     .collect::<Vec<_>>();
+    if cells.len() < settings.stars * 3 + 2 {
+        return None
+    }
+    let mut goals = Vec::new();
+    while goals.len() < settings.stars {
+        let pos = cells[rng.gen_range(0..cells.len())];
+        if !goals.contains(&pos) {
+            goals.push(pos);
+        }
+    }
+    let mut stars = goals.clone();
+    let mut player = cells[rng.gen_range(0..cells.len())];
+    if stars.contains(&player) {
+        return None
+    }
+
+    // Walk backwards from the solved position, pulling stars along now and then,
+    // until there have been about as many pulls as the level should take pushes
+    let mut pulls = Vec::new();
+    let mut pulled_count = 0;
+    let is_floor = |p: Position| floor[p.y][p.x];
+    for _ in 0..MAX_STEPS {
+        if pulled_count >= settings.difficulty && !stars.iter().any(|s| goals.contains(s)) {
+            break
+        }
+        let dir = Topology::Square.directions()[rng.gen_range(0..4)];
+        let next = player.move_in_direction(Topology::Square, dir);
+        if !is_floor(next) || stars.contains(&next) {
+            continue
+        }
+        let behind = player.move_in_direction(Topology::Square, dir.opposite());
+        let pulled = stars.iter().position(|&s| s == behind).filter(|_| rng.gen_bool(0.7));
+        if let Some(i) = pulled {
+            stars[i] = player;
+            pulled_count += 1;
+        }
+        pulls.push((dir, pulled.is_some()));
+        player = next;
+    }
+    // Stars that start on their goals make for dull levels
+    if stars.iter().any(|s| goals.contains(s)) || goals.contains(&player) {
+        return None
+    }
+
+    // Playing the pulls backwards as pushes solves the level
+    let solution = pulls.iter().rev().map(|&(dir, pull)| dir.opposite().lurd(pull)).collect::<String>();
+    let lines = floor.iter().enumerate().map(|(y, row)| {
+        row.iter().enumerate().map(|(x, &open)| {
+            let pos = Position::new(x, y);
+            if !open {
+                '#'
+            } else if pos == player {
+                '@'
+            } else if stars.contains(&pos) {
+                '$'
+            } else if goals.contains(&pos) {
+                '.'
+            } else {
+                ' '
+            }
+        }).collect::<String>()
+    }).collect::<Vec<_>>();
+    let mut level = Level::from_lines(lines.iter().map(|l| l.as_str()).collect(), Vec::new()).ok()?;
+    let shortest = optimizer::optimize(&level, &solution, Metric::Pushes, VICINITY, |_| true).ok()?;
+    level.set_metadata("Solution", shortest.lurd);
+    Some(level)
+}
+
+/// Lays out a room of `width` by `height` templates with a wall around it.
+/// Gives the floor cells as true, or None if the floor came out in more than one piece.
+fn room(rng: &mut StdRng, width: usize, height: usize) -> Option<Vec<Vec<bool>>> {
+    let mut map = vec![vec![0u8; width * 3 + 2]; height * 3 + 2];
+    for ty in 0..height {
+        for tx in 0..width {
+            let template = &TEMPLATES[rng.gen_range(0..TEMPLATES.len())];
+            let t = Transform::new(rng.gen_range(0..4), rng.gen_bool(0.5));
+            for (y, row) in template.iter().enumerate() {
+                for (x, c) in row.chars().enumerate() {
+                    let pos = t.position(Position::new(x, y), 3, 3);
+                    map[ty * 3 + pos.y + 1][tx * 3 + pos.x + 1] = if c == '#' { 0 } else { 1 };
+                }
+            }
+        }
+    }
+    let first = map.iter().enumerate().filter_map(|(y, row)| row.iter().position(|&c| c == 1).map(|x| (x, y))).next()?;
+    floodfill(&mut map, 1, 2, first.0, first.1, &Topology::Square.offsets());
+    if map.iter().any(|row| row.contains(&1)) {
+        return None
+    }
+    Some(map.into_iter().map(|row| row.into_iter().map(|c| c == 2).collect()).collect())
+}
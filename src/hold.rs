@@ -0,0 +1,102 @@
+//! Keys held down. A held move key repeats the move at a steady rate, and held camera keys pan the
+//! camera smoothly. Both go by how much time has passed rather than by frames or by the system's
+//! key repeat, so they feel the same at any frame rate.
+
+use std::time::Duration;
+
+use keys::Action;
+use Camera;
+
+/// How fast the camera pans, in pixels a second.
+pub const PAN_SPEED: i64 = 240;
+
+/// The longest a frame counts as, so a stall doesn't fire a burst of moves or throw the camera across the level.
+pub const MAX_FRAME: Duration = Duration::from_millis(250);
+
+pub fn is_move(action: Action) -> bool {
+    matches!(action, Action::MoveUp | Action::MoveDown | Action::MoveLeft | Action::MoveRight |
+                     Action::MoveUpLeft | Action::MoveUpRight | Action::MoveDownLeft | Action::MoveDownRight)
+}
+
+pub fn is_camera(action: Action) -> bool {
+    matches!(action, Action::CameraUp | Action::CameraDown | Action::CameraLeft | Action::CameraRight)
+}
+
+/// Repeats the move whose key is held down: once after `delay`, then `rate` times a second.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Repeat {
+    delay: Duration,
+    interval: Duration,
+    /// The move being repeated, and how long its key has been held
+    held: Option<(Action, Duration)>,
+}
+impl Repeat {
+    pub fn new(delay: Duration, rate: u32) -> Repeat {
+        Repeat {
+            delay,
+            interval: Duration::from_secs(1) / rate.max(1),
+            held: None,
+        }
+    }
+    /// Starts timing a move key that was just pressed. The first move is made by the press itself.
+    pub fn press(&mut self, action: Action) {
+        if is_move(action) {
+            self.held = Some((action, Duration::from_secs(0)));
+        }
+    }
+    /// Takes the actions whose keys are held down now and the time since the last frame,
+    /// and returns the move to repeat and how many times to make it this frame.
+    pub fn update(&mut self, held: &[Action], elapsed: Duration) -> Option<(Action, u32)> {
+        let elapsed = elapsed.min(MAX_FRAME);
+        let (action, before) = match self.held {
+            Some((action, before)) if held.contains(&action) => (action, before),
+            // Once the key is let go, a move key still held down takes over, after the delay like a new press
+            _ => {
+                self.held = held.iter().cloned().find(|&a| is_move(a)).map(|a| (a, Duration::from_secs(0)));
+                return None
+            },
+        };
+        let after = before + elapsed;
+        self.held = Some((action, after));
+        let times = self.repeats(after) - self.repeats(before);
+        if times > 0 { Some((action, times)) } else { None }
+    }
+    /// How many times a move repeats in the time its key has been held.
+    fn repeats(&self, held: Duration) -> u32 {
+        if held < self.delay {
+            0
+        } else {
+            1 + ((held - self.delay).as_nanos() / self.interval.as_nanos()) as u32
+        }
+    }
+}
+
+/// Pans the camera while camera keys are held, keeping the part of a pixel it hasn't moved yet.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pan {
+    /// In millionths of a pixel, so short frames add up exactly
+    x: i64,
+    y: i64,
+}
+impl Pan {
+    pub fn new() -> Pan {
+        Pan::default()
+    }
+    pub fn update(&mut self, camera: &mut Camera, held: &[Action], elapsed: Duration) {
+        let step = PAN_SPEED * elapsed.min(MAX_FRAME).as_micros() as i64;
+        // A key and a button held for the same way don't pan any faster
+        let way = |forward, back| (held.contains(&forward) as i64 - held.contains(&back) as i64) * step;
+        let dx = way(Action::CameraLeft, Action::CameraRight);
+        let dy = way(Action::CameraUp, Action::CameraDown);
+        if dx == 0 && dy == 0 {
+            *self = Pan::new();
+            return
+        }
+        self.x += dx;
+        self.y += dy;
+        let (x, y) = (self.x / 1_000_000, self.y / 1_000_000);
+        self.x -= x * 1_000_000;
+        self.y -= y * 1_000_000;
+        camera.pan(x as i32, y as i32);
+    }
+}
@@ -0,0 +1,235 @@
+//! The things keys do in the game, and which keys do them. Keys are named the way SDL names them,
+//! like "Up", "W" or "Keypad 7", so the bindings can be kept in a text file the player can edit.
+//! Gamepad buttons and sticks get names too, like "Pad A", so they're bound the same way.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hold::Repeat;
+use save;
+
+/// How long a move key is held, in milliseconds, before the move starts repeating.
+pub const DEFAULT_REPEAT_DELAY: u64 = 250;
+/// How many times a second a held move repeats.
+pub const DEFAULT_REPEAT_RATE: u32 = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveUp,
+    MoveDown,
+    MoveLeft,
+    MoveRight,
+    MoveUpLeft,
+    MoveUpRight,
+    MoveDownLeft,
+    MoveDownRight,
+    SwitchPusher,
+    CameraUp,
+    CameraDown,
+    CameraLeft,
+    CameraRight,
+    FollowPusher,
+    CenterCamera,
+    Minimap,
+    Undo,
+    Redo,
+    Reset,
+    NextLevel,
+    PreviousLevel,
+    NextMode,
+    Generate,
+    ShadeUnreachable,
+    TurnLevel,
+    MirrorLevel,
+    OptimizeMoves,
+    OptimizePushes,
+    LevelBrowser,
+    OpenFile,
+    Settings,
+    Quit,
+}
+
+/// Every action, in the order the settings screen lists them.
+pub const ACTIONS: [Action; 32] = [
+    Action::MoveUp, Action::MoveDown, Action::MoveLeft, Action::MoveRight,
+    Action::MoveUpLeft, Action::MoveUpRight, Action::MoveDownLeft, Action::MoveDownRight,
+    Action::SwitchPusher, Action::CameraUp, Action::CameraDown, Action::CameraLeft, Action::CameraRight,
+    Action::FollowPusher, Action::CenterCamera, Action::Minimap,
+    Action::Undo, Action::Redo, Action::Reset, Action::NextLevel, Action::PreviousLevel, Action::NextMode, Action::Generate,
+    Action::ShadeUnreachable, Action::TurnLevel, Action::MirrorLevel, Action::OptimizeMoves, Action::OptimizePushes,
+    Action::LevelBrowser, Action::OpenFile, Action::Settings, Action::Quit,
+];
+
+impl Action {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Action::MoveUp => "Move up",
+            Action::MoveDown => "Move down",
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::MoveUpLeft => "Move up left",
+            Action::MoveUpRight => "Move up right",
+            Action::MoveDownLeft => "Move down left",
+            Action::MoveDownRight => "Move down right",
+            Action::SwitchPusher => "Switch pusher",
+            Action::CameraUp => "Camera up",
+            Action::CameraDown => "Camera down",
+            Action::CameraLeft => "Camera left",
+            Action::CameraRight => "Camera right",
+            Action::FollowPusher => "Follow pusher",
+            Action::CenterCamera => "Center camera",
+            Action::Minimap => "Minimap",
+            Action::Undo => "Undo",
+            Action::Redo => "Redo",
+            Action::Reset => "Reset level",
+            Action::NextLevel => "Next level",
+            Action::PreviousLevel => "Previous level",
+            Action::NextMode => "Next mode",
+            Action::Generate => "Generate level",
+            Action::ShadeUnreachable => "Shade unreachable",
+            Action::TurnLevel => "Turn level",
+            Action::MirrorLevel => "Mirror level",
+            Action::OptimizeMoves => "Optimize moves",
+            Action::OptimizePushes => "Optimize pushes",
+            Action::LevelBrowser => "Level browser",
+            Action::OpenFile => "Open file",
+            Action::Settings => "Settings",
+            Action::Quit => "Quit",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().cloned().find(|a| a.name().eq_ignore_ascii_case(name))
+    }
+    pub fn default_keys(&self) -> &'static [&'static str] {
+        match *self {
+            Action::MoveUp => &["Up", "Pad Up", "Pad Stick Up"],
+            Action::MoveDown => &["Down", "Pad Down", "Pad Stick Down"],
+            Action::MoveLeft => &["Left", "Pad Left", "Pad Stick Left"],
+            Action::MoveRight => &["Right", "Pad Right", "Pad Stick Right"],
+            // The keypad diagonals are for hexoban levels
+            Action::MoveUpLeft => &["Keypad 7"],
+            Action::MoveUpRight => &["Keypad 9"],
+            Action::MoveDownLeft => &["Keypad 1"],
+            Action::MoveDownRight => &["Keypad 3"],
+            Action::SwitchPusher => &["Tab", "Pad X"],
+            Action::CameraUp => &["W"],
+            Action::CameraDown => &["S"],
+            Action::CameraLeft => &["A"],
+            Action::CameraRight => &["D"],
+            Action::FollowPusher => &["T"],
+            Action::CenterCamera => &["C", "Pad Right Stick"],
+            Action::Minimap => &["I"],
+            Action::Undo => &["U", "Pad LB"],
+            Action::Redo => &["Y", "Pad RB"],
+            Action::Reset => &["Backspace", "Pad Back"],
+            Action::NextLevel => &["N"],
+            Action::PreviousLevel => &["B"],
+            Action::NextMode => &["M"],
+            Action::Generate => &["G"],
+            Action::ShadeUnreachable => &["H"],
+            Action::TurnLevel => &["R"],
+            Action::MirrorLevel => &["F"],
+            Action::OptimizeMoves => &["O"],
+            Action::OptimizePushes => &["P"],
+            Action::LevelBrowser => &["V", "Pad Start"],
+            Action::OpenFile => &["L"],
+            Action::Settings => &["K"],
+            Action::Quit => &["Escape"],
+        }
+    }
+}
+
+/// Which keys do each action. An action can have any number of keys, but a key only does one action.
+/// Also how held move keys repeat, since that's kept in the same file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bindings {
+    path: PathBuf,
+    keys: HashMap<Action, Vec<String>>,
+    /// In milliseconds
+    pub repeat_delay: u64,
+    /// Moves a second
+    pub repeat_rate: u32,
+}
+impl Bindings {
+    pub fn defaults(path: PathBuf) -> Bindings {
+        let keys = ACTIONS.iter().map(|&a| (a, a.default_keys().iter().map(|k| k.to_string()).collect())).collect();
+        Bindings { path, keys, repeat_delay: DEFAULT_REPEAT_DELAY, repeat_rate: DEFAULT_REPEAT_RATE }
+    }
+    /// Loads the bindings kept at `path`, where each line is "<action>: <key>, <key>, ...",
+    /// or "Repeat delay: <milliseconds>" or "Repeat rate: <moves a second>".
+    /// Actions the file doesn't mention keep their default keys, and lines that aren't bindings are skipped.
+    pub fn load(path: PathBuf) -> Bindings {
+        let mut bindings = Bindings::defaults(path);
+        if let Ok(f) = File::open(&bindings.path) {
+            for line in BufReader::new(f).lines().map_while(Result::ok) {
+                let (name, keys) = match line.find(':') {
+                    Some(i) => (&line[..i], &line[i+1..]),
+                    None => continue,
+                };
+                let name = name.trim();
+                if name.eq_ignore_ascii_case("Repeat delay") {
+                    bindings.repeat_delay = keys.trim().parse().unwrap_or(bindings.repeat_delay);
+                } else if name.eq_ignore_ascii_case("Repeat rate") {
+                    // A rate of 0 would never repeat, so it's taken as a mistake
+                    bindings.repeat_rate = keys.trim().parse().ok().filter(|&r| r > 0).unwrap_or(bindings.repeat_rate);
+                } else if let Some(action) = Action::from_name(name) {
+                    bindings.clear(action);
+                    // Keys are split by ", " rather than just a comma, so the comma key can be bound too
+                    for key in keys.split(", ").map(str::trim).filter(|k| !k.is_empty()) {
+                        bindings.bind(action, key);
+                    }
+                }
+            }
+        }
+        bindings
+    }
+    /// The action `key` does, if any.
+    pub fn action(&self, key: &str) -> Option<Action> {
+        ACTIONS.iter().cloned().find(|a| self.keys(*a).iter().any(|k| k.eq_ignore_ascii_case(key)))
+    }
+    pub fn keys(&self, action: Action) -> &[String] {
+        self.keys.get(&action).map(|k| &k[..]).unwrap_or(&[])
+    }
+    /// Makes `key` do `action`, taking it away from whatever it did before.
+    pub fn bind(&mut self, action: Action, key: &str) {
+        for keys in self.keys.values_mut() {
+            keys.retain(|k| !k.eq_ignore_ascii_case(key));
+        }
+        self.keys.entry(action).or_default().push(key.to_string());
+    }
+    pub fn clear(&mut self, action: Action) {
+        self.keys.insert(action, Vec::new());
+    }
+    /// Puts every action back on its default keys. How moves repeat is left alone.
+    pub fn reset(&mut self) {
+        let (delay, rate) = (self.repeat_delay, self.repeat_rate);
+        *self = Bindings::defaults(self.path.clone());
+        self.repeat_delay = delay;
+        self.repeat_rate = rate;
+    }
+    /// Times held move keys the way these settings say.
+    pub fn repeat(&self) -> Repeat {
+        Repeat::new(Duration::from_millis(self.repeat_delay), self.repeat_rate)
+    }
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut f = File::create(&self.path)?;
+        writeln!(f, "# Each line is an action and the keys that do it, split by \", \"")?;
+        writeln!(f, "# Held move keys repeat after the delay, in milliseconds, at the rate, in moves a second")?;
+        for &action in ACTIONS.iter() {
+            writeln!(f, "{}: {}", action.name(), self.keys(action).join(", "))?;
+        }
+        writeln!(f, "Repeat delay: {}", self.repeat_delay)?;
+        writeln!(f, "Repeat rate: {}", self.repeat_rate)?;
+        Ok(())
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    save::data_dir().join("keys.txt")
+}
@@ -0,0 +1,1117 @@
+//! The rules of the game, the level format and the tools built on them, without any drawing,
+//! so other front ends and the benchmarks can use them too.
+
+extern crate rand;
+
+use std::cell::{Ref, RefCell};
+use std::cmp::PartialEq;
+use std::fs::File;
+use std::io::prelude::*;
+use std::mem;
+use std::time::Instant;
+
+pub mod bitboard;
+pub mod canonical;
+pub mod cli;
+pub mod difficulty;
+pub mod files;
+pub mod follow;
+pub mod generator;
+pub mod hold;
+pub mod keys;
+pub mod minimap;
+pub mod optimizer;
+pub mod pad;
+pub mod reachability;
+pub mod save;
+pub mod transform;
+pub mod verifier;
+pub mod watch;
+
+use keys::Action;
+use reachability::Reachability;
+use transform::Transform;
+
+/// The levels that come with the game.
+#[cfg(feature = "bundled-levels")]
+pub const LEVELS: &str = include_str!("../levels.txt");
+pub const WIDTH: u32 = 900;
+pub const HEIGHT: u32 = 675;
+pub const HALF_WIDTH: u32 = 450;
+pub const HALF_HEIGHT: u32 = 337;
+pub const TILE_WIDTH: u32 = 64;
+pub const TILE_HEIGHT: u32 = 64;
+/// Seconds on the clock in countdown mode for levels without a "Time limit"
+pub const DEFAULT_TIME_LIMIT: u64 = 180;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+    UpLeft,
+    UpRight,
+    DownLeft,
+    DownRight,
+}
+
+impl Direction {
+    /// The letter for a step in this direction in LURD notation, in upper case for pushes.
+    /// Hexoban has no standard letters for the diagonals, so they're written with the
+    /// keys that surround S on a QWERTY keyboard instead.
+    pub fn lurd(&self, push: bool) -> char {
+        let c = match *self {
+            Direction::Up => 'u',
+            Direction::Down => 'd',
+            Direction::Left => 'l',
+            Direction::Right => 'r',
+            Direction::UpLeft => 'q',
+            Direction::UpRight => 'e',
+            Direction::DownLeft => 'z',
+            Direction::DownRight => 'c',
+        };
+        if push { c.to_ascii_uppercase() } else { c }
+    }
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::Up => Direction::Down,
+            Direction::Down => Direction::Up,
+            Direction::Left => Direction::Right,
+            Direction::Right => Direction::Left,
+            Direction::UpLeft => Direction::DownRight,
+            Direction::UpRight => Direction::DownLeft,
+            Direction::DownLeft => Direction::UpRight,
+            Direction::DownRight => Direction::UpLeft,
+        }
+    }
+    /// The direction for a LURD letter, in either case.
+    pub fn from_lurd(c: char) -> Option<Direction> {
+        match c.to_ascii_lowercase() {
+            'u' => Some(Direction::Up),
+            'd' => Some(Direction::Down),
+            'l' => Some(Direction::Left),
+            'r' => Some(Direction::Right),
+            'q' => Some(Direction::UpLeft),
+            'e' => Some(Direction::UpRight),
+            'z' => Some(Direction::DownLeft),
+            'c' => Some(Direction::DownRight),
+            _ => None,
+        }
+    }
+}
+
+/// The shape of the grid a level is played on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// Classic Sokoban with square cells and four directions.
+    Square,
+    /// Hexoban with hexagonal cells and six directions.
+    /// Cells are kept in "doubled" coordinates, exactly as they are laid out in the level text:
+    /// every other column holds a cell, and each row is shifted by one column from its neighbours.
+    Hex,
+}
+impl Topology {
+    pub fn from_name(name: &str) -> Result<Topology, String> {
+        match name.to_lowercase().as_str() {
+            "sokoban" | "square" => Ok(Topology::Square),
+            "hexoban" | "hex" => Ok(Topology::Hex),
+            _ => Err(format!("'{}' is an invalid topology", name)),
+        }
+    }
+    pub fn directions(&self) -> &'static [Direction] {
+        match *self {
+            Topology::Square => &[Direction::Up, Direction::Right, Direction::Down, Direction::Left],
+            Topology::Hex => &[Direction::UpLeft, Direction::UpRight, Direction::Right,
+                               Direction::DownRight, Direction::DownLeft, Direction::Left],
+        }
+    }
+    /// The (x, y) offset of a step in `dir`, or None if you can't move that way on this grid.
+    pub fn offset(&self, dir: Direction) -> Option<(i32, i32)> {
+        match (*self, dir) {
+            (Topology::Square, Direction::Up) => Some((0, -1)),
+            (Topology::Square, Direction::Right) => Some((1, 0)),
+            (Topology::Square, Direction::Down) => Some((0, 1)),
+            (Topology::Square, Direction::Left) => Some((-1, 0)),
+            (Topology::Hex, Direction::UpLeft) => Some((-1, -1)),
+            (Topology::Hex, Direction::UpRight) => Some((1, -1)),
+            (Topology::Hex, Direction::Right) => Some((2, 0)),
+            (Topology::Hex, Direction::DownRight) => Some((1, 1)),
+            (Topology::Hex, Direction::DownLeft) => Some((-1, 1)),
+            (Topology::Hex, Direction::Left) => Some((-2, 0)),
+            _ => None,
+        }
+    }
+    pub fn offsets(&self) -> Vec<(i32, i32)> {
+        self.directions().iter().filter_map(|&d| self.offset(d)).collect()
+    }
+    /// Hexoban has no straight up or down, so the arrow keys pick the diagonal
+    /// on the side the player is already facing.
+    pub fn resolve(&self, dir: Direction, facing: Direction) -> Direction {
+        let facing_left = matches!(facing, Direction::Left | Direction::UpLeft | Direction::DownLeft);
+        match (*self, dir) {
+            (Topology::Hex, Direction::Up) if facing_left => Direction::UpLeft,
+            (Topology::Hex, Direction::Up) => Direction::UpRight,
+            (Topology::Hex, Direction::Down) if facing_left => Direction::DownLeft,
+            (Topology::Hex, Direction::Down) => Direction::DownRight,
+            _ => dir,
+        }
+    }
+    /// The top left corner of the cell at (x, y) on the rendered level, in pixels.
+    pub fn tile_origin(&self, x: usize, y: usize) -> (i32, i32) {
+        match *self {
+            Topology::Square => ((x * TILE_WIDTH as usize) as i32, (y * TILE_HEIGHT as usize) as i32),
+            Topology::Hex => ((x * TILE_WIDTH as usize / 2) as i32, (y * TILE_HEIGHT as usize) as i32),
+        }
+    }
+    /// The size in pixels of a rendered level with the given dimensions.
+    pub fn surface_size(&self, width: usize, height: usize) -> (u32, u32) {
+        match *self {
+            Topology::Square => (width as u32 * TILE_WIDTH, height as u32 * TILE_HEIGHT),
+            Topology::Hex => ((width as u32 + 1) * TILE_WIDTH / 2, height as u32 * TILE_HEIGHT),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Wall,
+    Player,
+    PlayerOnGoal,
+    Star,
+    StarOnGoal,
+    Goal,
+    OutsideFloor,
+    InsideFloor,
+    /// Stars and pushers that move onto ice keep sliding until something stops them
+    Ice,
+    /// Floor that can only be entered by moving in its direction
+    OneWay(Direction),
+    /// Swallows the first star pushed into it and turns into floor. Pushers can't walk into it.
+    Hole,
+    /// Moves whatever lands on it to the other teleporter with the same number
+    Teleporter(u8),
+}
+impl Tile {
+    /// Character to tile mapping taken from http://sokobano.de/wiki/index.php?title=Level_format
+    /// I renamed Box to Star to not conflict with the Box type.
+    /// Ice, one-way floors, holes and teleporters are our own additions.
+    pub fn from_char(c: char) -> Result<Tile, String> {
+        match c {
+            '#' => Ok(Tile::Wall),
+            '@' => Ok(Tile::Player),
+            '+' => Ok(Tile::PlayerOnGoal),
+            '$' => Ok(Tile::Star),
+            '*' => Ok(Tile::StarOnGoal),
+            '.' => Ok(Tile::Goal),
+            ' ' => Ok(Tile::OutsideFloor),
+            '~' => Ok(Tile::Ice),
+            '^' => Ok(Tile::OneWay(Direction::Up)),
+            'v' => Ok(Tile::OneWay(Direction::Down)),
+            '<' => Ok(Tile::OneWay(Direction::Left)),
+            '>' => Ok(Tile::OneWay(Direction::Right)),
+            'o' => Ok(Tile::Hole),
+            '0'..='9' => Ok(Tile::Teleporter(c as u8 - b'0')),
+            _ => Err(format!("'{}' is an invalid tile", c)),
+        }
+    }
+    pub fn as_char(&self) -> char {
+        match *self {
+            Tile::Wall => '#',
+            Tile::Player => '@',
+            Tile::PlayerOnGoal => '+',
+            Tile::Star => '$',
+            Tile::StarOnGoal => '*',
+            Tile::Goal => '.',
+            Tile::OutsideFloor | Tile::InsideFloor => ' ',
+            Tile::Ice => '~',
+            Tile::OneWay(Direction::Up) => '^',
+            Tile::OneWay(Direction::Down) => 'v',
+            Tile::OneWay(Direction::Left) => '<',
+            Tile::OneWay(Direction::Right) => '>',
+            Tile::OneWay(_) => ' ',
+            Tile::Hole => 'o',
+            Tile::Teleporter(id) => (b'0' + id) as char,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Position {
+    pub x: usize,
+    pub y: usize,
+}
+impl Position {
+    pub fn new(x: usize, y: usize) -> Position {
+        Position {x, y}
+    }
+    pub fn move_in_direction(&self, topology: Topology, dir: Direction) -> Position {
+        let (x_off, y_off) = topology.offset(dir).expect("Invalid direction for this topology");
+        Position::new((self.x as i32 + x_off) as usize, (self.y as i32 + y_off) as usize)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Player {
+    pub position: Position,
+    pub direction: Direction,
+}
+impl Player {
+    pub fn new(position: Position, direction: Direction) -> Player {
+        Player {
+            position,
+            direction,
+        }
+    }
+    pub fn move_in_direction(&self, topology: Topology, direction: Direction) -> Player {
+        Player::new(self.position.move_in_direction(topology, direction), direction)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Star {
+    pub position: Position,
+    /// Colored stars have to end up on a goal of the same color
+    pub color: Option<u8>,
+}
+impl Star {
+    pub fn new(position: Position) -> Star {
+        Star { position, color: None }
+    }
+    pub fn move_in_direction(&self, topology: Topology, direction: Direction) -> Star {
+        Star { position: self.position.move_in_direction(topology, direction), ..*self }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Goal {
+    pub position: Position,
+    pub color: Option<u8>,
+}
+impl Goal {
+    pub fn new(position: Position) -> Goal {
+        Goal { position, color: None }
+    }
+}
+
+/// The tint used to draw stars and goals of the given color.
+pub fn color_tint(color: u8) -> (u8, u8, u8) {
+    const PALETTE: [(u8, u8, u8); 6] = [
+        (230, 70, 60),
+        (70, 120, 235),
+        (240, 205, 50),
+        (170, 85, 210),
+        (245, 145, 40),
+        (60, 205, 215),
+    ];
+    PALETTE[color as usize % PALETTE.len()]
+}
+
+/// Parses a "Star colors" or "Goal colors" list, which has one entry for each star or goal
+/// in reading order. Entries are color numbers, or `-` for an uncolored star or goal.
+pub fn parse_colors(list: &str, count: usize, what: &str) -> Result<Vec<Option<u8>>, String> {
+    let colors = list.split_whitespace()
+                     .map(|c| if c == "-" {
+                         Ok(None)
+                     } else {
+                         c.parse().map(Some).map_err(|_| format!("Invalid level: '{}' is an invalid {} color", c, what))
+                     })
+                     .collect::<Result<Vec<_>, String>>()?;
+    if colors.len() != count {
+        return Err(format!("Invalid level: Level has {} {}s but {} {} colors", count, what, colors.len(), what));
+    }
+    Ok(colors)
+}
+
+/// Looks up the value of a "Key: value" metadata line. Keys are case insensitive.
+pub fn metadata_value<'a>(metadata: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    metadata.iter().find(|m| m.0.eq_ignore_ascii_case(key)).map(|m| m.1.as_str())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameState {
+    pub players: Vec<Player>,
+    /// Index into `players` of the pusher that is being controlled
+    pub active: usize,
+    pub stars: Vec<Star>,
+    pub goals: Vec<Goal>,
+    /// Holes that have swallowed a star and are floor now
+    pub filled_holes: Vec<Position>,
+    pub steps: usize,
+    pub pushes: usize,
+}
+impl GameState {
+    pub fn new(players: Vec<Player>, stars: Vec<Star>, goals: Vec<Goal>,  steps: usize) -> GameState {
+        GameState { players, active: 0, stars, goals, filled_holes: Vec::new(), steps, pushes: 0 }
+    }
+    pub fn player(&self) -> &Player {
+        &self.players[self.active]
+    }
+    pub fn switch_player(&mut self) {
+        self.active = (self.active + 1) % self.players.len();
+    }
+}
+
+/// A single step taken by one of the pushers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    pub pusher: usize,
+    pub direction: Direction,
+    pub push: bool,
+}
+impl Move {
+    pub fn new(pusher: usize, direction: Direction, push: bool) -> Move {
+        Move { pusher, direction, push }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Level {
+    pub width: usize,
+    pub height: usize,
+    pub topology: Topology,
+    pub map: Vec<Vec<Tile>>,
+    pub start_state: GameState,
+    pub metadata: Vec<(String, String)>,
+}
+impl Level {
+    pub fn from_lines(lines: Vec<&str>, metadata: Vec<(String, String)>) -> Result<Level, String> {
+        let topology = match metadata_value(&metadata, "Topology") {
+            Some(name) => Topology::from_name(name)?,
+            None => Topology::Square,
+        };
+        let longest_line_len = lines.iter()
+                                .map(|l| l.len())
+                                .max()
+                                .ok_or("Invalid level: Level is empty")?;
+        let mut map = Vec::with_capacity(lines.len());
+        let mut stars = Vec::new();
+        let mut goals = Vec::new();
+        let mut player_positions = Vec::new();
+        let mut specials = Vec::new();
+        let mut hex_parity = None;
+        for (y, line) in lines.iter().enumerate() {
+            let mut row = Vec::with_capacity(line.len());
+            for (x, tile) in line.chars().enumerate() {
+                let tile = Tile::from_char(tile)?;
+                if topology == Topology::Hex && tile != Tile::OutsideFloor {
+                    // Hexoban cells sit on every other column, so every tile has to share the first one's parity
+                    let parity = (x + y) % 2;
+                    if *hex_parity.get_or_insert(parity) != parity {
+                        return Err(format!("Invalid level: '{}' at ({}, {}) is not on the hexoban grid", line.chars().nth(x).unwrap(), x, y));
+                    }
+                }
+                if tile == Tile::PlayerOnGoal || tile == Tile::StarOnGoal || tile == Tile::Goal {
+                    goals.push(Goal::new(Position::new(x, y)));
+                }
+                if tile == Tile::Player || tile == Tile::PlayerOnGoal {
+                    // This tile is a starting position. Multiban levels have more than one.
+                    player_positions.push(Position::new(x, y));
+                    row.push(Tile::OutsideFloor);
+                } else if tile == Tile::Star || tile == Tile::StarOnGoal {
+                    stars.push(Star::new(Position::new(x, y)));
+                    row.push(Tile::OutsideFloor);
+                } else if tile == Tile::Goal {
+                    row.push(Tile::OutsideFloor);
+                } else if tile != Tile::Wall && tile != Tile::OutsideFloor {
+                    // Special floors go back on the map once the floodfill has found the inside
+                    specials.push((Position::new(x, y), tile));
+                    row.push(Tile::OutsideFloor);
+                } else {
+                    row.push(tile);
+                }
+            }
+            if line.len() < longest_line_len {
+                for _ in 0..(longest_line_len - line.len()) {
+                    row.push(Tile::OutsideFloor);
+                }
+            }
+            map.push(row);
+        }
+        if player_positions.is_empty() {
+            return Err("Invalid level: Level has no starting position".to_string());
+        }
+        if let Some(list) = metadata_value(&metadata, "Star colors") {
+            let colors = parse_colors(list, stars.len(), "star")?;
+            for (star, color) in stars.iter_mut().zip(colors) {
+                star.color = color;
+            }
+        }
+        if let Some(list) = metadata_value(&metadata, "Goal colors") {
+            let colors = parse_colors(list, goals.len(), "goal")?;
+            for (goal, color) in goals.iter_mut().zip(colors) {
+                goal.color = color;
+            }
+        }
+        for &(_, tile) in &specials {
+            match tile {
+                Tile::OneWay(dir) if topology.offset(dir).is_none() => {
+                    return Err(format!("Invalid level: {:?} one-way floors don't fit on this grid", dir));
+                },
+                Tile::Teleporter(id) if specials.iter().filter(|s| s.1 == tile).count() != 2 => {
+                    return Err(format!("Invalid level: Teleporter {} needs exactly one partner", id));
+                },
+                _ => (),
+            }
+        }
+        let height = map.len();
+        for pos in &player_positions {
+            floodfill(&mut map, Tile::OutsideFloor, Tile::InsideFloor, pos.x, pos.y, &topology.offsets());
+        }
+        for (pos, tile) in specials {
+            map[pos.y][pos.x] = tile;
+        }
+        let start_state = GameState::new(player_positions.into_iter().map(|pos| Player::new(pos, Direction::Right)).collect(),
+                                         stars,
+                                         goals,
+                                         0);
+        Ok(Level { map, width: longest_line_len, height, topology, start_state, metadata })
+    }
+    pub fn is_wall(&self, x: i32, y: i32) -> bool {
+        if y < 0 || y >= self.height as i32 || x < 0 || x >= self.width as i32 {
+            false
+        } else {
+            self.map[y as usize][x as usize] == Tile::Wall
+        }
+    }
+    /// The tile at (x, y), or None if that's off the map.
+    pub fn tile(&self, x: i32, y: i32) -> Option<Tile> {
+        if y < 0 || y >= self.height as i32 || x < 0 || x >= self.width as i32 {
+            None
+        } else {
+            Some(self.map[y as usize][x as usize])
+        }
+    }
+    /// Where something that lands on the teleporter at `pos` comes out.
+    pub fn teleporter_exit(&self, pos: Position) -> Option<Position> {
+        let id = match self.map[pos.y][pos.x] {
+            Tile::Teleporter(id) => id,
+            _ => return None,
+        };
+        for (y, row) in self.map.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                if tile == Tile::Teleporter(id) && Position::new(x, y) != pos {
+                    return Some(Position::new(x, y));
+                }
+            }
+        }
+        None
+    }
+    /// The map with `state` drawn onto it, in the level file format.
+    /// A star or pusher standing on ice or a teleporter hides it.
+    pub fn map_lines(&self, state: &GameState) -> Vec<String> {
+        let mut lines = Vec::with_capacity(self.height);
+        for (y, row) in self.map.iter().enumerate() {
+            let mut line = String::with_capacity(row.len());
+            for (x, tile) in row.iter().enumerate() {
+                let pos = Position::new(x, y);
+                let goal = state.goals.iter().any(|g| g.position == pos);
+                let tile = if state.players.iter().any(|p| p.position == pos) {
+                    if goal { Tile::PlayerOnGoal } else { Tile::Player }
+                } else if state.stars.iter().any(|s| s.position == pos) {
+                    if goal { Tile::StarOnGoal } else { Tile::Star }
+                } else if goal {
+                    Tile::Goal
+                } else if state.filled_holes.contains(&pos) {
+                    Tile::InsideFloor
+                } else {
+                    *tile
+                };
+                line.push(tile.as_char());
+            }
+            lines.push(line.trim_end().to_string());
+        }
+        lines
+    }
+    /// The cell under the point (x, y) of the rendered level, in pixels.
+    pub fn cell_at(&self, x: i32, y: i32) -> Option<Position> {
+        if x < 0 || y < 0 {
+            return None
+        }
+        let row = y as usize / TILE_HEIGHT as usize;
+        let candidates = match self.topology {
+            Topology::Square => vec![x as usize / TILE_WIDTH as usize],
+            // Hexoban cells are two half columns wide, so the point is in one of two cells, and only one of them is on the grid
+            Topology::Hex => {
+                let half = x as usize / (TILE_WIDTH as usize / 2);
+                vec![half, half.wrapping_sub(1)]
+            },
+        };
+        candidates.into_iter()
+                  .find(|&col| self.tile(col as i32, row as i32).is_some_and(|t| t != Tile::OutsideFloor))
+                  .map(|col| Position::new(col, row))
+    }
+    /// The level written out in the level file format, metadata first.
+    pub fn text(&self) -> String {
+        let mut lines = self.metadata.iter().map(|m| format!("{}: {}", m.0, m.1)).collect::<Vec<_>>();
+        lines.extend(self.map_lines(&self.start_state));
+        lines.join("\n")
+    }
+    /// The level in the position `state`, as level text. Only the metadata it needs to load back
+    /// the same is kept: the topology, and the colors of the stars and goals in their new reading order.
+    pub fn position_text(&self, state: &GameState) -> String {
+        let mut lines = Vec::new();
+        if self.topology == Topology::Hex {
+            lines.push("Topology: Hexoban".to_string());
+        }
+        let colors = |mut cells: Vec<(Position, Option<u8>)>| {
+            cells.sort_by_key(|c| (c.0.y, c.0.x));
+            cells.iter().map(|c| c.1.map_or("-".to_string(), |color| color.to_string())).collect::<Vec<_>>().join(" ")
+        };
+        if state.stars.iter().any(|s| s.color.is_some()) {
+            lines.push(format!("Star colors: {}", colors(state.stars.iter().map(|s| (s.position, s.color)).collect())));
+        }
+        if state.goals.iter().any(|g| g.color.is_some()) {
+            lines.push(format!("Goal colors: {}", colors(state.goals.iter().map(|g| (g.position, g.color)).collect())));
+        }
+        lines.extend(self.map_lines(state));
+        lines.join("\n")
+    }
+    /// Whether the level has ice, one-way floors, holes or teleporters.
+    pub fn has_special_floors(&self) -> bool {
+        self.map.iter().any(|row| row.iter().any(|&t| !matches!(t, Tile::Wall | Tile::InsideFloor | Tile::OutsideFloor)))
+    }
+    pub fn has_holes(&self) -> bool {
+        self.map.iter().any(|row| row.contains(&Tile::Hole))
+    }
+    pub fn metadata_number(&self, key: &str) -> Option<usize> {
+        metadata_value(&self.metadata, key).and_then(|v| v.parse().ok())
+    }
+    /// The moves and pushes of the best known solution, taken from the "Solution" metadata.
+    pub fn par(&self) -> Option<(usize, usize)> {
+        let moves = verifier::parse(metadata_value(&self.metadata, "Solution")?).ok()?;
+        Some((moves.len(), moves.iter().filter(|m| m.2).count()))
+    }
+    pub fn set_metadata(&mut self, key: &str, value: String) {
+        match self.metadata.iter_mut().find(|m| m.0.eq_ignore_ascii_case(key)) {
+            Some(m) => m.1 = value,
+            None => self.metadata.push((key.to_string(), value)),
+        }
+    }
+    pub fn time_limit(&self) -> u64 {
+        self.metadata_number("Time limit").map_or(DEFAULT_TIME_LIMIT, |t| t as u64)
+    }
+}
+
+/// The ways a level can be played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Challenge {
+    /// No limits at all
+    Free,
+    /// The level's "Move limit" and "Push limit" can't be exceeded
+    Budget,
+    /// The level has to be solved before its "Time limit" runs out
+    Countdown,
+    /// The result is compared to the level's best known "Solution"
+    Par,
+}
+impl Challenge {
+    pub fn next(&self) -> Challenge {
+        match *self {
+            Challenge::Free => Challenge::Budget,
+            Challenge::Budget => Challenge::Countdown,
+            Challenge::Countdown => Challenge::Par,
+            Challenge::Par => Challenge::Free,
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Challenge::Free => "Free play",
+            Challenge::Budget => "Budget",
+            Challenge::Countdown => "Countdown",
+            Challenge::Par => "Par",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Camera {
+    pub x_offset: i32,
+    pub y_offset: i32,
+    pub max_x_pan: i32,
+    pub max_y_pan: i32,
+    pub speed: i32,
+}
+impl Camera {
+    pub fn new(x_offset: i32, y_offset: i32, max_x_pan: i32, max_y_pan: i32, speed: i32) -> Camera {
+        Camera {
+            x_offset,
+            y_offset,
+            max_x_pan,
+            max_y_pan,
+            speed,
+        }
+    }
+    pub fn move_up(&mut self) {
+        if self.y_offset < self.max_y_pan {
+            self.y_offset += self.speed;
+        }
+    }
+    pub fn move_down(&mut self) {
+        if self.y_offset > -self.max_y_pan {
+            self.y_offset -= self.speed;
+        }
+    }
+    pub fn move_right(&mut self) {
+        if self.x_offset > -self.max_x_pan {
+            self.x_offset -= self.speed;
+        }
+    }
+    pub fn move_left(&mut self) {
+        if self.x_offset < self.max_x_pan {
+            self.x_offset += self.speed;
+        }
+    }
+    /// Moves the view by `dx` and `dy` pixels, as far as it can pan.
+    pub fn pan(&mut self, dx: i32, dy: i32) {
+        self.x_offset = (self.x_offset + dx).max(-self.max_x_pan).min(self.max_x_pan);
+        self.y_offset = (self.y_offset + dy).max(-self.max_y_pan).min(self.max_y_pan);
+    }
+    /// Looks at the same spot as `other`, as far as this camera can pan.
+    pub fn pan_like(&mut self, other: &Camera) {
+        self.x_offset = other.x_offset.max(-self.max_x_pan).min(self.max_x_pan);
+        self.y_offset = other.y_offset.max(-self.max_y_pan).min(self.max_y_pan);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Game {
+    pub level: Level,
+    pub state: GameState,
+    pub camera: Camera,
+    pub history: Vec<Move>,
+    /// Moves taken back by undo, the latest last, until a new move is made
+    pub undone: Vec<Move>,
+    pub challenge: Challenge,
+    pub started: Instant,
+    /// How the level is turned from the way it was loaded
+    pub view: Transform,
+    /// Where the active pusher could walk when it was last asked
+    reach: RefCell<Option<Reachability>>,
+}
+impl Game {
+    pub fn new(level: Level, state: GameState, camera: Camera) -> Game {
+        Game {
+            level,
+            state,
+            camera,
+            history: Vec::new(),
+            undone: Vec::new(),
+            challenge: Challenge::Free,
+            started: Instant::now(),
+            view: Transform::new(0, false),
+            reach: RefCell::new(None),
+        }
+    }
+    pub fn from_level(level: Level) -> Game {
+        // The camera can pan far enough to bring each edge of the level a tile onto the screen, and no further
+        let (w, h) = level.topology.surface_size(level.width, level.height);
+        Game::new(level.clone(), 
+                  level.start_state, 
+                  Camera::new(0, 
+                              0, 
+                              (w as i32 / 2 - HALF_WIDTH as i32).max(0) + TILE_WIDTH as i32,
+                              (h as i32 / 2 - HALF_HEIGHT as i32).max(0) + TILE_HEIGHT as i32,
+                              5))
+    }
+    pub fn with_challenge(mut self, challenge: Challenge) -> Game {
+        self.challenge = challenge;
+        self
+    }
+    /// The game so far, turned by `t`. Hexoban levels can't be turned and come back as they were.
+    pub fn transformed(self, t: Transform) -> Game {
+        let level = match t.level(&self.level) {
+            Ok(level) => level,
+            Err(_) => return self,
+        };
+        let (w, h) = (self.level.width, self.level.height);
+        Game {
+            state: t.state(&self.state, w, h),
+            history: self.history.iter().map(|m| Move::new(m.pusher, t.direction(m.direction), m.push)).collect(),
+            undone: self.undone.iter().map(|m| Move::new(m.pusher, t.direction(m.direction), m.push)).collect(),
+            view: self.view.then(t),
+            started: self.started,
+            ..Game::from_level(level).with_challenge(self.challenge)
+        }
+    }
+    /// Where the active pusher can walk to right now. It's only worked out again after something moved.
+    pub fn reachability(&self) -> Ref<'_, Reachability> {
+        let stale = self.reach.borrow().as_ref().is_none_or(|r| !r.is_for(&self.state));
+        if stale {
+            *self.reach.borrow_mut() = Some(Reachability::new(&self.level, &self.state));
+        }
+        Ref::map(self.reach.borrow(), |r| r.as_ref().unwrap())
+    }
+    /// Walks the active pusher to `pos` the shortest way there without pushing anything.
+    pub fn walk_to(&mut self, pos: Position) {
+        let path = self.reachability().path_to(pos);
+        for dir in path.unwrap_or_default() {
+            self.make_move(dir);
+        }
+    }
+    /// Takes back the last move by playing the level again without it. Moves can't be reversed directly,
+    /// since stars fall into holes and things slide over ice. Returns whether there was a move to take back.
+    pub fn undo(&mut self) -> bool {
+        let mut history = self.history.clone();
+        let last = match history.pop() {
+            Some(m) => m,
+            None => return false,
+        };
+        let active = self.state.active;
+        let mut undone = mem::take(&mut self.undone);
+        self.state = self.level.start_state.clone();
+        self.history.clear();
+        for m in history {
+            self.state.active = m.pusher;
+            self.make_move(m.direction);
+        }
+        self.state.active = active;
+        undone.push(last);
+        self.undone = undone;
+        true
+    }
+    /// Makes the last move taken back by `undo` again. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        let m = match self.undone.pop() {
+            Some(m) => m,
+            None => return false,
+        };
+        let active = self.state.active;
+        let undone = mem::take(&mut self.undone);
+        self.state.active = m.pusher;
+        self.make_move(m.direction);
+        self.state.active = active;
+        self.undone = undone;
+        true
+    }
+    /// Does `action` if it's something done to the game itself: moving, undoing, switching pushers or panning.
+    /// Everything else, like changing levels, is up to the front end.
+    pub fn act(&mut self, action: Action) {
+        match action {
+            Action::MoveUp => self.make_move(Direction::Up),
+            Action::MoveDown => self.make_move(Direction::Down),
+            Action::MoveLeft => self.make_move(Direction::Left),
+            Action::MoveRight => self.make_move(Direction::Right),
+            Action::MoveUpLeft => self.make_move(Direction::UpLeft),
+            Action::MoveUpRight => self.make_move(Direction::UpRight),
+            Action::MoveDownLeft => self.make_move(Direction::DownLeft),
+            Action::MoveDownRight => self.make_move(Direction::DownRight),
+            Action::Undo => {
+                self.undo();
+            },
+            Action::Redo => {
+                self.redo();
+            },
+            // Switch to the next pusher in multiban levels
+            Action::SwitchPusher => self.state.switch_player(),
+            Action::CameraUp => self.move_camera(Direction::Up),
+            Action::CameraDown => self.move_camera(Direction::Down),
+            Action::CameraLeft => self.move_camera(Direction::Left),
+            Action::CameraRight => self.move_camera(Direction::Right),
+            _ => (),
+        }
+    }
+    /// Nudges the camera one step. Holding a camera key pans smoothly instead, see `hold::Pan`.
+    pub fn move_camera(&mut self, dir: Direction) {
+        match dir {
+            Direction::Up => self.camera.move_up(),
+            Direction::Down => self.camera.move_down(),
+            Direction::Left => self.camera.move_left(),
+            Direction::Right => self.camera.move_right(),
+            _ => (),
+        }
+    }
+    pub fn make_move(&mut self, direction: Direction) {
+        let topology = self.level.topology;
+        let active = self.state.active;
+        let direction = topology.resolve(direction, self.state.player().direction);
+        let (x_off, y_off) = match topology.offset(direction) {
+            Some(offset) => offset,
+            None => return,
+        };
+        self.state.players[active].direction = direction;
+        let (new_x, new_y) = (self.state.players[active].position.x as i32 + x_off, 
+                              self.state.players[active].position.y as i32 + y_off);
+        if self.can_enter(new_x, new_y, direction) && !self.is_open_hole(new_x, new_y) {
+            let star = self.star_at(new_x, new_y);
+            let push = star.is_some();
+            if let Some(ind) = star {
+                if !self.is_blocked(new_x + x_off, new_y + y_off) && self.can_enter(new_x + x_off, new_y + y_off, direction) {
+                    self.state.stars[ind] = self.state.stars[ind].move_in_direction(topology, direction);
+                    self.settle_star(ind, direction);
+                } else {
+                    return
+                }
+            }
+            self.state.players[active] = self.state.players[active].move_in_direction(topology, direction);
+            self.settle_player(direction);
+            self.state.steps += 1;
+            if push {
+                self.state.pushes += 1;
+            }
+            self.history.push(Move::new(active, direction, push));
+            self.undone.clear();
+        }
+    }
+    /// Lets a star that was just pushed in `direction` slide over ice, fall into a hole or teleport.
+    fn settle_star(&mut self, ind: usize, direction: Direction) {
+        let topology = self.level.topology;
+        let (x_off, y_off) = topology.offset(direction).unwrap();
+        loop {
+            let pos = self.state.stars[ind].position;
+            let (x, y) = (pos.x as i32, pos.y as i32);
+            match self.level.tile(x, y) {
+                Some(Tile::Hole) if self.is_open_hole(x, y) => {
+                    self.state.stars.remove(ind);
+                    self.state.filled_holes.push(pos);
+                },
+                Some(Tile::Teleporter(_)) => {
+                    let exit = self.level.teleporter_exit(pos).unwrap();
+                    if !self.is_blocked(exit.x as i32, exit.y as i32) {
+                        self.state.stars[ind].position = exit;
+                    }
+                },
+                Some(Tile::Ice) if !self.is_blocked(x + x_off, y + y_off) && self.can_enter(x + x_off, y + y_off, direction) => {
+                    self.state.stars[ind] = self.state.stars[ind].move_in_direction(topology, direction);
+                    continue
+                },
+                _ => (),
+            }
+            return
+        }
+    }
+    /// Lets the active pusher slide over ice or teleport after stepping in `direction`.
+    /// Sliding pushers stop in front of stars instead of pushing them.
+    fn settle_player(&mut self, direction: Direction) {
+        let topology = self.level.topology;
+        let active = self.state.active;
+        let (x_off, y_off) = topology.offset(direction).unwrap();
+        loop {
+            let pos = self.state.players[active].position;
+            let (x, y) = (pos.x as i32, pos.y as i32);
+            match self.level.tile(x, y) {
+                Some(Tile::Teleporter(_)) => {
+                    let exit = self.level.teleporter_exit(pos).unwrap();
+                    if !self.is_blocked(exit.x as i32, exit.y as i32) {
+                        self.state.players[active].position = exit;
+                    }
+                },
+                Some(Tile::Ice) if !self.is_blocked(x + x_off, y + y_off)
+                                   && self.can_enter(x + x_off, y + y_off, direction)
+                                   && !self.is_open_hole(x + x_off, y + y_off) => {
+                    self.state.players[active] = self.state.players[active].move_in_direction(topology, direction);
+                    continue
+                },
+                _ => (),
+            }
+            return
+        }
+    }
+    /// Whether a star or pusher moving in `direction` may step onto (x, y), not counting stars.
+    /// Nothing can leave the map, even where a level isn't closed off by walls.
+    fn can_enter(&self, x: i32, y: i32, direction: Direction) -> bool {
+        !self.level.is_wall(x, y) && !self.is_player(x, y) && match self.level.tile(x, y) {
+            Some(Tile::OneWay(dir)) => dir == direction,
+            Some(_) => true,
+            None => false,
+        }
+    }
+    fn is_open_hole(&self, x: i32, y: i32) -> bool {
+        self.level.tile(x, y) == Some(Tile::Hole) && !self.state.filled_holes.contains(&Position::new(x as usize, y as usize))
+    }
+    fn is_player(&self, x: i32, y: i32) -> bool {
+        self.state.players.iter().any(|p| p.position == Position::new(x as usize, y as usize))
+    }
+    /// The index of the star at (x, y), if there is one.
+    fn star_at(&self, x: i32, y: i32) -> Option<usize> {
+        self.state.stars.iter().position(|s| s.position == Position::new(x as usize, y as usize))
+    }
+    fn is_blocked(&self, x: i32, y: i32) -> bool {
+        self.level.is_wall(x, y) || self.is_player(x, y) || self.star_at(x, y).is_some()
+    }
+    pub fn time_left(&self) -> u64 {
+        self.level.time_limit().saturating_sub(self.started.elapsed().as_secs())
+    }
+    pub fn out_of_moves(&self) -> bool {
+        self.level.metadata_number("Move limit").is_some_and(|limit| self.state.steps >= limit)
+    }
+    pub fn out_of_pushes(&self) -> bool {
+        self.level.metadata_number("Push limit").is_some_and(|limit| self.state.pushes >= limit)
+    }
+    /// Whether the challenge was lost. Using up the last move on the solution still counts as a win.
+    pub fn failed(&self) -> bool {
+        !self.solved() && match self.challenge {
+            Challenge::Budget => self.out_of_moves() || self.out_of_pushes(),
+            Challenge::Countdown => self.time_left() == 0,
+            Challenge::Free | Challenge::Par => false,
+        }
+    }
+    pub fn failure_text(&self) -> &'static str {
+        if self.challenge == Challenge::Countdown {
+            "Out of time!"
+        } else if self.out_of_moves() {
+            "Out of moves!"
+        } else {
+            "Out of pushes!"
+        }
+    }
+    /// The counters shown under the level number while playing.
+    pub fn status_text(&self) -> String {
+        let (moves, pushes) = (self.state.steps, self.state.pushes);
+        match self.challenge {
+            Challenge::Budget => {
+                let limit = |key| self.level.metadata_number(key).map_or("-".to_string(), |l| l.to_string());
+                format!("Moves {}/{}  Pushes {}/{}", moves, limit("Move limit"), pushes, limit("Push limit"))
+            },
+            Challenge::Countdown => {
+                let left = self.time_left();
+                format!("Time {}:{:02}  Moves {}  Pushes {}", left / 60, left % 60, moves, pushes)
+            },
+            Challenge::Par => match self.level.par() {
+                Some((par_moves, par_pushes)) => format!("Moves {}  Pushes {}  Par {}/{}", moves, pushes, par_moves, par_pushes),
+                None => format!("Moves {}  Pushes {}  No par known", moves, pushes),
+            },
+            Challenge::Free => format!("Moves {}  Pushes {}", moves, pushes),
+        }
+    }
+    /// How the level went, for the end of level screen.
+    pub fn result_text(&self) -> String {
+        let (moves, pushes) = (self.state.steps, self.state.pushes);
+        let counts = format!("{} moves, {} pushes", moves, pushes);
+        match self.challenge {
+            Challenge::Countdown if self.solved() => {
+                let left = self.time_left();
+                format!("{} with {}:{:02} to spare", counts, left / 60, left % 60)
+            },
+            Challenge::Par => match self.level.par() {
+                Some((par_moves, par_pushes)) if (moves, pushes) < (par_moves, par_pushes) => {
+                    format!("{} - better than par ({}/{})!", counts, par_moves, par_pushes)
+                },
+                Some((par_moves, par_pushes)) if (moves, pushes) == (par_moves, par_pushes) => format!("{} - right on par", counts),
+                Some((par_moves, _)) if moves > par_moves => format!("{} - {} moves over par", counts, moves - par_moves),
+                Some((_, par_pushes)) => format!("{} - {} pushes over par", counts, pushes - par_pushes),
+                None => counts,
+            },
+            _ => counts,
+        }
+    }
+    /// The moves each pusher made so far in LURD notation, indexed like `state.players`.
+    pub fn solutions(&self) -> Vec<String> {
+        let mut solutions = vec![String::new(); self.state.players.len()];
+        for m in &self.history {
+            solutions[m.pusher].push(m.direction.lurd(m.push));
+        }
+        solutions
+    }
+    pub fn solved(&self) -> bool {
+        let stars_home = self.state.stars.iter().all(|s| self.state.goals.iter().any(|g| g.position == s.position && g.color == s.color));
+        if self.level.has_holes() {
+            // Holes let a level hand out spare stars, so every goal has to be covered as well
+            stars_home && self.state.goals.iter().all(|g| self.state.stars.iter().any(|s| s.position == g.position))
+        } else {
+            stars_home
+        }
+    }
+}
+
+pub fn load_levels(levels: &str) -> Result<Vec<Level>, String> {
+    let mut parsed_levels = Vec::new();
+    let mut map_lines = Vec::new();
+    let mut metadata: Vec<(String, String)> = Vec::new();
+    // Whether the last line started a solution, which the next lines of moves carry on
+    let mut in_solution = false;
+    for line in levels.lines() {
+        let line = if let Some(i) = line.find(';') {
+            &line[0..i]
+        } else {
+            line
+        };
+        let line = line.trim_end();
+        if in_solution && is_solution_line(line) {
+            // Long solutions go on over several lines in .sok files
+            if let Some(m) = metadata.last_mut() {
+                m.1.push_str(line.trim());
+            }
+            continue
+        }
+        // .sok files can put the counts after the key, as in "Solution (moves 12, pushes 3):",
+        // or leave out the colon when the solution starts on the next line
+        in_solution = line.to_lowercase().starts_with("solution");
+        if in_solution {
+            let value = line.find(':').map_or("", |i| line[i+1..].trim());
+            metadata.push(("Solution".to_string(), value.to_string()));
+        } else if let Some(i) = line.find(':') {
+            // "Key: value" lines hold metadata about the level they're next to, like its topology
+            metadata.push((line[..i].trim().to_string(), line[i+1..].trim().to_string()));
+        } else if !line.is_empty() {
+            map_lines.push(line);
+        } else if line.is_empty() && !map_lines.is_empty() {
+            parsed_levels.push(Level::from_lines(map_lines, metadata)?);
+            map_lines = Vec::new();
+            metadata = Vec::new();
+        }
+    }
+    // The last level doesn't need a blank line after it
+    if !map_lines.is_empty() {
+        parsed_levels.push(Level::from_lines(map_lines, metadata)?);
+    }
+    Ok(parsed_levels)
+}
+
+/// Whether `line` is nothing but moves, like a line of a solution.
+fn is_solution_line(line: &str) -> bool {
+    line.chars().any(|c| Direction::from_lurd(c).is_some())
+        && line.chars().all(|c| c.is_whitespace() || c.is_ascii_digit() || Direction::from_lurd(c).is_some())
+}
+
+pub fn load_level_file(path: &str) -> Result<Vec<Level>, String> {
+    let mut contents = String::new();
+    File::open(path).and_then(|mut f| f.read_to_string(&mut contents)).map_err(|e| format!("{}", e))?;
+    load_levels(&contents)
+}
+
+/// The levels the game starts with.
+#[cfg(feature = "bundled-levels")]
+pub fn default_levels() -> Result<Vec<Level>, String> {
+    load_levels(LEVELS)
+}
+/// The levels the game starts with. They aren't built in, so they're read from levels.txt in the working directory.
+#[cfg(not(feature = "bundled-levels"))]
+pub fn default_levels() -> Result<Vec<Level>, String> {
+    load_level_file("levels.txt").map_err(|e| format!("levels.txt: {}", e))
+}
+
+/// The level number and mode shown at the top of the screen.
+pub fn hud_text(game: &Game, level_number: i32) -> String {
+    let players = game.state.players.len();
+    let mut text = format!("Level {}", level_number+1);
+    if players > 1 {
+        text.push_str(&format!(" - Pusher {}/{}", game.state.active+1, players));
+    }
+    if game.challenge != Challenge::Free {
+        text.push_str(&format!(" - {}", game.challenge.name()));
+    }
+    if !game.view.is_identity() {
+        text.push_str(&format!(" - {}", game.view.name()));
+    }
+    text
+}
+
+/// Replaces `old` with `new` in the area connected to (x, y), stepping by `offsets`.
+/// Works through a stack of its own instead of recursing, so big open levels can't overflow the real one.
+pub fn floodfill<T: PartialEq + Copy>(map: &mut [Vec<T>], old: T, new: T, x: usize, y: usize, offsets: &[(i32, i32)]) {
+    if map[y][x] == old {
+        map[y][x] = new;
+    }
+    let mut stack = vec![(x, y)];
+    while let Some((x, y)) = stack.pop() {
+        for &(x_off, y_off) in offsets {
+            let (nx, ny) = (x as i32 + x_off, y as i32 + y_off);
+            if nx >= 0 && ny >= 0 && (ny as usize) < map.len() && (nx as usize) < map[ny as usize].len()
+               && map[ny as usize][nx as usize] == old {
+                map[ny as usize][nx as usize] = new;
+                stack.push((nx as usize, ny as usize));
+            }
+        }
+    }
+}
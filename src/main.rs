@@ -1,93 +1,75 @@
 extern crate sdl2;
 extern crate fps_clock;
+#[cfg(feature = "native-dialogs")]
 extern crate tinyfiledialogs;
-#[macro_use]
-extern crate lazy_static;
+extern crate sokoban_rs;
 
 use std::cmp::PartialEq;
 use std::io;
 use std::io::prelude::*;
 use std::fs::File;
 use std::env;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::Instant;
 
-use sdl2::video::Window;
-use sdl2::render::Canvas;
+use sdl2::video::{Window, WindowContext};
+use sdl2::render::{BlendMode, Canvas, TextureCreator};
 use sdl2::EventPump;
 use sdl2::pixels::Color;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, LCTRLMOD, LSHIFTMOD, NOMOD, RCTRLMOD, RSHIFTMOD};
+use sdl2::controller::{Button, GameController};
+use sdl2::GameControllerSubsystem;
+use sdl2::mouse::MouseButton;
 use sdl2::image::INIT_PNG;
 use sdl2::rect::{Rect, Point};
-use sdl2::ttf::Sdl2TtfContext;
+use sdl2::ttf::{Font, Sdl2TtfContext};
 use sdl2::surface::Surface;
 use sdl2::pixels::PixelFormatEnum;
+#[cfg(feature = "embedded-assets")]
 use sdl2::rwops::RWops;
+#[cfg(feature = "embedded-assets")]
 use sdl2::image::ImageRWops;
+#[cfg(not(feature = "embedded-assets"))]
+use sdl2::image::LoadSurface;
 
 use fps_clock::FpsClock;
 
+use sokoban_rs::*;
+use sokoban_rs::difficulty::Difficulty;
+use sokoban_rs::files::{Entry, EntryKind, RecentFiles};
+use sokoban_rs::follow::Follow;
+use sokoban_rs::hold::{self, Pan};
+use sokoban_rs::keys::{self, Action, Bindings, ACTIONS};
+use sokoban_rs::minimap::{self, Minimap};
+use sokoban_rs::optimizer::{Metric, Progress};
+use sokoban_rs::pad::{self, Sticks};
+use sokoban_rs::save::{Record, SaveData};
+use sokoban_rs::transform::Transform;
+use sokoban_rs::watch::FileWatch;
+
 macro_rules! rect {
     ($x:expr, $y:expr, $w:expr, $h:expr) => (Rect::new($x as i32, $y as i32, $w as u32, $h as u32))
 }
 
-const LEVELS: &'static str = include_str!("../levels.txt");
-const SPRITESHEET_BYTES: &'static [u8] = include_bytes!("../resources/images/sokoban_spritesheet.png");
-const FONT_BYTES: &'static [u8] = include_bytes!("../resources/font/swansea.ttf");
-const WIDTH: u32 = 900;
-const HEIGHT: u32 = 675;
-const HALF_WIDTH: u32 = 450;
-const HALF_HEIGHT: u32 = 337;
-const TILE_WIDTH: u32 = 64;
-const TILE_HEIGHT: u32 = 64;
+#[cfg(feature = "embedded-assets")]
+const SPRITESHEET_BYTES: &[u8] = include_bytes!("../resources/images/sokoban_spritesheet.png");
+#[cfg(feature = "embedded-assets")]
+const FONT_BYTES: &[u8] = include_bytes!("../resources/font/swansea.ttf");
+#[cfg(not(feature = "embedded-assets"))]
+const SPRITESHEET_PATH: &str = "resources/images/sokoban_spritesheet.png";
+#[cfg(not(feature = "embedded-assets"))]
+const FONT_PATH: &str = "resources/font/swansea.ttf";
 
-lazy_static! {
-    static ref BACKGROUND_COLOR: Color = Color::RGB(115, 139, 139);
-}
+const BACKGROUND_COLOR: Color = Color { r: 115, g: 139, b: 139, a: 255 };
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Direction {
-    Up,
-    Down,
-    Left,
-    Right
-}
-impl Direction {
-    fn as_offset(&self) -> (i32, i32) {
-        match *self {
-            Direction::Up => (0, -1),
-            Direction::Right => (1, 0),
-            Direction::Down => (0, 1),
-            Direction::Left => (-1, 0),
-        }
-    }
+/// Where a thing's picture is on the spritesheet.
+trait Sprite {
+    fn spritesheet_rect(&self) -> Rect;
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum Tile {
-    Wall,
-    Player,
-    PlayerOnGoal,
-    Star,
-    StarOnGoal,
-    Goal,
-    OutsideFloor,
-    InsideFloor
-}
-impl Tile {
-    /// Character to tile mapping taken from http://sokobano.de/wiki/index.php?title=Level_format
-    /// I renamed Box to Star to not conflict with the Box type.
-    fn from_char(c: char) -> Result<Tile, String> {
-        match c {
-            '#' => Ok(Tile::Wall),
-            '@' => Ok(Tile::Player),
-            '+' => Ok(Tile::PlayerOnGoal),
-            '$' => Ok(Tile::Star),
-            '*' => Ok(Tile::StarOnGoal),
-            '.' => Ok(Tile::Goal),
-            ' ' => Ok(Tile::OutsideFloor),
-            _ => Err(format!("'{}' is an invalid tile", c)),
-        }
-    }
+impl Sprite for Tile {
     fn spritesheet_rect(&self) -> Rect {
         match *self {
             Tile::Wall => rect!(448, 64, 64, 64),
@@ -98,347 +80,507 @@ impl Tile {
         }
     }
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Position {
-    x: usize,
-    y: usize,
-}
-impl Position {
-    fn new(x: usize, y: usize) -> Position {
-        Position {x, y}
-    }
-    fn move_in_direction(&self, dir: Direction) -> Position {
-        match dir {
-            Direction::Down => Position {y: self.y+1, ..*self},
-            Direction::Up => Position {y: self.y-1, ..*self},
-            Direction::Left => Position {x: self.x-1, ..*self},
-            Direction::Right => Position {x: self.x+1, ..*self},
-        }
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Player {
-    position: Position,
-    direction: Direction,
-}
-impl Player {
-    fn new(position: Position, direction: Direction) -> Player {
-        Player {
-            position,
-            direction,
-        }
-    }
-    fn move_in_direction(&self, direction: Direction) -> Player {
-        Player::new(self.position.move_in_direction(direction), direction)
-    }
+impl Sprite for Player {
     fn spritesheet_rect(&self) -> Rect {
         match self.direction {
-            Direction::Down => rect!(554, 208, 42, 50),
+            Direction::Down | Direction::DownLeft | Direction::DownRight => rect!(554, 208, 42, 50),
             Direction::Left => rect!(543, 440, 45, 50),
             Direction::Right => rect!(512, 108, 45, 50),
-            Direction::Up => rect!(554, 158, 42, 50),
+            Direction::Up | Direction::UpLeft | Direction::UpRight => rect!(554, 158, 42, 50),
         }
     }
 }
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Star {
-    position: Position
-}
-impl Star {
-    fn new(position: Position) -> Star {
-        Star { position }
-    }
-    fn move_in_direction(&self, direction: Direction) -> Star {
-        Star::new(self.position.move_in_direction(direction))
-    }
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Goal {
-    position: Position
-}
-impl Goal {
-    fn new(position: Position) -> Goal {
-        Goal { position }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct GameState {
-    player: Player,
-    stars: Vec<Star>,
-    goals: Vec<Goal>,
-    steps: usize,
-}
-impl GameState {
-    fn new(player: Player, stars: Vec<Star>, goals: Vec<Goal>,  steps: usize) -> GameState {
-        GameState { player, stars, goals, steps }
-    }
-}
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Level {
-    width: usize,
-    height: usize,
-    map: Vec<Vec<Tile>>,
-    start_state: GameState,
-}
-impl Level {
-    fn from_lines(lines: Vec<&str>) -> Result<Level, String> {
-        let longest_line_len = lines.iter()
-                                .map(|l| l.len())
-                                .max()
-                                .ok_or_else(|| "Invalid level: Level is empty")?;
-        let mut map = Vec::with_capacity(lines.len());
-        let mut stars = Vec::new();
-        let mut goals = Vec::new();
-        let mut player_pos = None;
-        for (y, line) in lines.iter().enumerate() {
-            let mut row = Vec::with_capacity(line.len());
-            for (x, tile) in line.chars().enumerate() {
-                let tile = Tile::from_char(tile)?;
-                if tile == Tile::Player || tile == Tile::PlayerOnGoal {
-                    // This tile is the starting position
-                    player_pos = Some(Position::new(x, y));
-                    row.push(Tile::OutsideFloor);
-                } else if tile == Tile::Star || tile == Tile::StarOnGoal {
-                    stars.push(Star::new(Position::new(x, y)));
-                    row.push(Tile::OutsideFloor);
-                } else if tile == Tile::PlayerOnGoal 
-                          || tile == Tile::StarOnGoal 
-                          || tile == Tile::Goal {
-                    goals.push(Goal::new(Position::new(x, y)));
-                    row.push(Tile::OutsideFloor);
-                } else {
-                    row.push(tile);
-                }
-            }
-            if line.len() < longest_line_len {
-                for _ in 0..(longest_line_len - line.len()) {
-                    row.push(Tile::OutsideFloor);
-                }
-            }
-            map.push(row);
-        }
-        let pos = player_pos.ok_or_else(|| "Invalid level: Level has no starting position")?;
-        let start_state = GameState::new(Player::new(pos, Direction::Right),
-                                         stars,
-                                         goals,
-                                         0);
-        let height = map.len();
-        floodfill(&mut map, Tile::OutsideFloor, Tile::InsideFloor, pos.x, pos.y);
-        Ok(Level { map, width: longest_line_len, height, start_state})
-    }
-    fn is_wall(&self, x: i32, y: i32) -> bool {
-        if y < 0 || y >= self.height as i32 || x < 0 || x > self.height as i32{
-            false
-        } else {
-            self.map[y as usize][x as usize] == Tile::Wall
+impl Sprite for Star {
+    fn spritesheet_rect(&self) -> Rect {
+        match self.color {
+            // The grey crate takes a tint much better than the green one
+            Some(_) => rect!(384, 256, 64, 64),
+            None => Tile::Star.spritesheet_rect(),
         }
     }
 }
-
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Camera {
-    x_offset: i32,
-    y_offset: i32,
-    max_x_pan: i32,
-    max_y_pan: i32,
-    speed: i32,
-}
-impl Camera {
-    fn new(x_offset: i32, y_offset: i32, max_x_pan: i32, max_y_pan: i32, speed: i32) -> Camera {
-        Camera {
-            x_offset,
-            y_offset,
-            max_x_pan,
-            max_y_pan,
-            speed,
-        }
-    }
-    fn move_up(&mut self) {
-        if self.y_offset < self.max_y_pan {
-            self.y_offset += self.speed;
-        }
-    }
-    fn move_down(&mut self) {
-        if self.y_offset > -self.max_y_pan {
-            self.y_offset -= self.speed;
-        }
-    }
-    fn move_right(&mut self) {
-        if self.x_offset > -self.max_x_pan {
-            self.x_offset -= self.speed;
-        }
-    }
-    fn move_left(&mut self) {
-        if self.x_offset < self.max_x_pan {
-            self.x_offset += self.speed;
+impl Sprite for Goal {
+    fn spritesheet_rect(&self) -> Rect {
+        match self.color {
+            Some(_) => rect!(40, 576, 20, 20),
+            None => Tile::Goal.spritesheet_rect(),
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-struct Game {
-    level: Level,
-    state: GameState,
-    camera: Camera,
-    camera_moving: bool,
-    camera_direction: Direction,
-}
-impl Game {
-    fn new(level: Level, state: GameState, camera: Camera) -> Game {
-        Game { level, state, camera, camera_moving: false, camera_direction: Direction::Left}
-    }
-    fn from_level(level: Level) -> Game {
-        let h = level.height;
-        let w = level.width;
-        Game::new(level.clone(), 
-                  level.start_state, 
-                  Camera::new(0, 
-                              0, 
-                              (HALF_HEIGHT as i32 - (h/2) as i32).abs() + TILE_HEIGHT as i32,
-                              (HALF_WIDTH as i32 - (w/2) as i32).abs() + TILE_WIDTH as i32,
-                              5))
-    }
-    fn step(&mut self, event: &Event) {
-        if self.camera_moving {
-            let d = self.camera_direction;
-            self.move_camera(d);
-        }
-        match *event {
-            Event::KeyUp{..} => self.camera_moving = false,
-            // Move the player
-            Event::KeyDown{keycode: Some(Keycode::Up), ..} => self.make_move(Direction::Up),
-            Event::KeyDown{keycode: Some(Keycode::Down), ..} => self.make_move(Direction::Down),
-            Event::KeyDown{keycode: Some(Keycode::Left), ..} => self.make_move(Direction::Left),
-            Event::KeyDown{keycode: Some(Keycode::Right), ..} => self.make_move(Direction::Right),
-            // Move the camera
-            Event::KeyDown{keycode: Some(Keycode::W), ..} => self.move_camera(Direction::Up),
-            Event::KeyDown{keycode: Some(Keycode::S), ..} => self.move_camera(Direction::Down),
-            Event::KeyDown{keycode: Some(Keycode::A), ..} => self.move_camera(Direction::Left),
-            Event::KeyDown{keycode: Some(Keycode::D), ..} => self.move_camera(Direction::Right),
-            _ => ()
-        }
-    }
-    fn move_camera(&mut self, dir: Direction) {
-        self.camera_direction = dir;
-        self.camera_moving = true;
-        match dir {
-            Direction::Up => self.camera.move_up(),
-            Direction::Down => self.camera.move_down(),
-            Direction::Left => self.camera.move_left(),
-            Direction::Right => self.camera.move_right(),
-        }
-    }
-    fn render_to_surface<'a>(&self, spritesheet_surf: &Surface<'a>) -> Surface<'static> {
+/// The parts of a game that need SDL.
+trait Screen {
+    fn render_to_surface<'a>(&self, spritesheet_surf: &Surface<'a>, shade_unreachable: bool) -> Surface<'static>;
+}
+impl Screen for Game {
+    /// Draws the level. With `shade_unreachable` the floor the active pusher can't walk to is darkened.
+    fn render_to_surface<'a>(&self, spritesheet_surf: &Surface<'a>, shade_unreachable: bool) -> Surface<'static> {
         let level = &self.level;
         let state = &self.state;
         let map = &level.map;
-        let surf = Surface::new((level.width * 64) as u32, 
-                                    (level.height * 64) as u32, 
+        let topology = level.topology;
+        let (surf_width, surf_height) = topology.surface_size(level.width, level.height);
+        let surf = Surface::new(surf_width, 
+                                    surf_height, 
                                     PixelFormatEnum::ABGR1555 /* <- I have no clue if this is right or not */).unwrap();
         let mut canvas = surf.into_canvas().unwrap();
         let texture_creator = canvas.texture_creator();
-        let spritesheet = texture_creator.create_texture_from_surface(spritesheet_surf).unwrap();
-        canvas.set_draw_color(*BACKGROUND_COLOR);
+        let mut spritesheet = texture_creator.create_texture_from_surface(spritesheet_surf).unwrap();
+        canvas.set_draw_color(BACKGROUND_COLOR);
         canvas.clear();
         for (y, row) in map.iter().enumerate() {
             for (x, tile) in row.iter().enumerate() {
                 match *tile {
                     Tile::OutsideFloor => (),
                     Tile::InsideFloor | Tile::Wall => {
-                        canvas.copy(&spritesheet, tile.spritesheet_rect(), rect!(x*64, y*64, 64, 64)).unwrap();
+                        let (x, y) = topology.tile_origin(x, y);
+                        canvas.copy(&spritesheet, tile.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+                    },
+                    Tile::Ice => {
+                        let (x, y) = topology.tile_origin(x, y);
+                        spritesheet.set_color_mod(170, 220, 255);
+                        canvas.copy(&spritesheet, Tile::InsideFloor.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+                        spritesheet.set_color_mod(255, 255, 255);
+                    },
+                    Tile::OneWay(dir) => {
+                        let (x_off, y_off) = topology.offset(dir).unwrap();
+                        let (x, y) = topology.tile_origin(x, y);
+                        canvas.copy(&spritesheet, Tile::InsideFloor.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+                        // Draw a chevron pointing the way you're allowed to go
+                        let (ux, uy) = (x_off.signum(), y_off.signum());
+                        let (cx, cy) = (x + 32, y + 32);
+                        canvas.set_draw_color(Color::RGB(60, 60, 60));
+                        for w in -2..3 {
+                            let tip = Point::new(cx + ux*14 + w*ux, cy + uy*14 + w*uy);
+                            canvas.draw_line(Point::new(cx - ux*6 - uy*14 + w*ux, cy - uy*6 + ux*14 + w*uy), tip).unwrap();
+                            canvas.draw_line(Point::new(cx - ux*6 + uy*14 + w*ux, cy - uy*6 - ux*14 + w*uy), tip).unwrap();
+                        }
+                    },
+                    Tile::Hole => {
+                        let pos = Position::new(x, y);
+                        let (x, y) = topology.tile_origin(x, y);
+                        canvas.copy(&spritesheet, Tile::InsideFloor.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+                        if !state.filled_holes.contains(&pos) {
+                            canvas.set_draw_color(Color::RGB(25, 25, 30));
+                            canvas.fill_rect(rect!(x+8, y+8, 48, 48)).unwrap();
+                        }
+                    },
+                    Tile::Teleporter(id) => {
+                        let (x, y) = topology.tile_origin(x, y);
+                        canvas.copy(&spritesheet, Tile::InsideFloor.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+                        let (r, g, b) = color_tint(id);
+                        canvas.set_draw_color(Color::RGB(r, g, b));
+                        for inset in &[6, 9, 16, 19] {
+                            canvas.draw_rect(rect!(x+inset, y+inset, 64-2*inset, 64-2*inset)).unwrap();
+                        }
                     },
                     _ => ()
                 }
+                let pos = Position::new(x, y);
+                if shade_unreachable && *tile != Tile::Wall && *tile != Tile::OutsideFloor
+                   && !self.reachability().is_reachable(pos) && !state.stars.iter().any(|s| s.position == pos) {
+                    let (x, y) = topology.tile_origin(x, y);
+                    canvas.set_draw_color(Color::RGBA(0, 0, 0, 100));
+                    canvas.set_blend_mode(BlendMode::Blend);
+                    canvas.fill_rect(rect!(x, y, 64, 64)).unwrap();
+                    canvas.set_blend_mode(BlendMode::None);
+                }
             }
         }
         for goal in &state.goals {
-            let (x, y) = (&goal.position.x, &goal.position.y);
-            canvas.copy(&spritesheet, Tile::Goal.spritesheet_rect(), rect!(x*64+22, y*64+22, 20, 20)).unwrap();
+            let (x, y) = topology.tile_origin(goal.position.x, goal.position.y);
+            if let Some(color) = goal.color {
+                let (r, g, b) = color_tint(color);
+                spritesheet.set_color_mod(r, g, b);
+            }
+            canvas.copy(&spritesheet, goal.spritesheet_rect(), rect!(x+22, y+22, 20, 20)).unwrap();
+            spritesheet.set_color_mod(255, 255, 255);
         }
         for star in &state.stars {
-            let (x, y) = (&star.position.x, &star.position.y);
-            canvas.copy(&spritesheet, Tile::Star.spritesheet_rect(), rect!(x*64, y*64, 64, 64)).unwrap();
+            let (x, y) = topology.tile_origin(star.position.x, star.position.y);
+            if let Some(color) = star.color {
+                let (r, g, b) = color_tint(color);
+                spritesheet.set_color_mod(r, g, b);
+            }
+            canvas.copy(&spritesheet, star.spritesheet_rect(), rect!(x, y, 64, 64)).unwrap();
+            spritesheet.set_color_mod(255, 255, 255);
+        }
+        for (i, player) in state.players.iter().enumerate() {
+            let (player_x, player_y) = topology.tile_origin(player.position.x, player.position.y);
+            let player_rect = player.spritesheet_rect();
+            let w = player_rect.width();
+            let h = player_rect.height();
+            let r = Rect::from_center(Point::new(player_x+32, player_y+32), w, h);
+            // Shade the pushers that aren't being controlled so the active one stands out
+            if i != state.active {
+                spritesheet.set_color_mod(120, 120, 120);
+            }
+            canvas.copy(&spritesheet, player_rect, r).unwrap();
+            spritesheet.set_color_mod(255, 255, 255);
         }
-        let player = state.player;
-        let (player_x, player_y) = (player.position.x, player.position.y);
-        let player_rect = player.spritesheet_rect();
-        let w = player_rect.width();
-        let h = player_rect.height();
-        let r = Rect::from_center(Point::new((player_x*64+32) as i32, (player_y*64+32) as i32), w, h);
-        canvas.copy(&spritesheet, player_rect, r).unwrap();
         canvas.into_surface()
     }
-    fn make_move(&mut self, direction: Direction) -> () {
-        self.state.player.direction = direction;
-        let (x_off, y_off) = direction.as_offset();
-        let (new_x, new_y) = (self.state.player.position.x as i32 + x_off, 
-                              self.state.player.position.y as i32 + y_off);
-        if !self.level.is_wall(new_x, new_y) {
-            let star = Star::new(Position::new(new_x as usize, new_y as usize));
-            if self.state.stars.contains(&star) {
-                if !self.is_blocked(new_x + x_off, new_y + y_off) {
-                    let ind = self.state.stars.iter().position(|&s| s == star).unwrap();
-                    self.state.stars[ind] = self.state.stars[ind].move_in_direction(direction);
-                } else {
-                    return
-                }
-            }
-            self.state.player = self.state.player.move_in_direction(direction);
+}
+
+/// An optimizer running in the background for one of the levels.
+struct Optimization {
+    level_number: i32,
+    metric: Metric,
+    progress: Receiver<Progress>,
+}
+impl Optimization {
+    fn start(level_number: i32, level: &Level, solution: String, metric: Metric) -> Optimization {
+        println!("optimizing level {} for {}", level_number+1, metric.name());
+        Optimization { level_number, metric, progress: optimizer::spawn(level.clone(), solution, metric) }
+    }
+}
+
+/// Switches to a new set of levels. What was worked out in the background for the old ones is dropped,
+/// since level numbers would now point at other levels: the ratings, the rater and the optimizer.
+fn replace_levels(levels: Vec<Level>,
+                  parsed_levels: &mut Vec<Level>,
+                  ratings: &mut Vec<Option<Difficulty>>,
+                  rating: &mut Option<Receiver<(usize, Difficulty)>>,
+                  optimization: &mut Option<Optimization>) {
+    *ratings = vec![None; levels.len()];
+    *parsed_levels = levels;
+    *rating = None;
+    *optimization = None;
+}
+
+/// How many levels the level browser shows at once.
+const BROWSER_ROWS: usize = 11;
+
+/// The orders the level browser can list levels in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Number,
+    Difficulty,
+}
+
+/// The list of levels with their difficulty, opened with V.
+struct Browser {
+    /// Level indices in the order they're listed
+    order: Vec<usize>,
+    /// The position in `order` of the highlighted level
+    selected: usize,
+    sort: Sort,
+}
+impl Browser {
+    fn new(count: usize, current: usize) -> Browser {
+        Browser { order: (0..count).collect(), selected: current, sort: Sort::Number }
+    }
+    /// Puts the levels back in order, keeping the same level highlighted.
+    /// Levels that haven't been rated yet go last when sorting by difficulty.
+    fn sort(&mut self, ratings: &[Option<Difficulty>]) {
+        let current = self.order[self.selected];
+        match self.sort {
+            Sort::Number => self.order.sort(),
+            Sort::Difficulty => self.order.sort_by_key(|&i| (ratings[i].is_none(), ratings[i].map(|r| r.score), i)),
         }
+        self.selected = self.order.iter().position(|&i| i == current).unwrap_or(0);
     }
-    fn is_blocked(&self, x: i32, y: i32) -> bool {
-        self.level.is_wall(x, y) || self.state.stars.contains(&Star::new(Position::new(x as usize, y as usize)))
+    fn select(&mut self, offset: i32) {
+        let last = self.order.len() as i32 - 1;
+        self.selected = (self.selected as i32 + offset).max(0).min(last) as usize;
     }
-    fn solved(&self) -> bool {
-        self.state.stars.iter().all(|s| self.state.goals.contains(&Goal::new(s.position)))
+    fn title(&self) -> String {
+        let sort = match self.sort {
+            Sort::Number => "number",
+            Sort::Difficulty => "difficulty",
+        };
+        format!("Levels by {} - S: sort, Enter: play, E: export, Esc: close", sort)
+    }
+    /// The rows on screen, with whether each is the highlighted one.
+    fn rows(&self, levels: &[Level], ratings: &[Option<Difficulty>]) -> Vec<(String, bool)> {
+        let first = self.selected.saturating_sub(BROWSER_ROWS / 2).min(self.order.len().saturating_sub(BROWSER_ROWS));
+        self.order.iter().enumerate().skip(first).take(BROWSER_ROWS).map(|(row, &i)| {
+            let stars = levels[i].start_state.stars.len();
+            let rating = match ratings[i] {
+                Some(r) => format!("difficulty {}", r.score),
+                None => "rating...".to_string(),
+            };
+            (format!("Level {} - {} stars - {}", i+1, stars, rating), row == self.selected)
+        }).collect()
     }
 }
 
-fn load_levels(levels: &str) -> Result<Vec<Level>, String> {
-    let mut parsed_levels = Vec::new();
-    let mut map_lines = Vec::new();
-    for line in levels.lines() {
-        let line = line.trim_right();
-        let line = if let Some(i) = line.find(';') {
-            &line[0..i]
+/// The screen for changing which keys do what, opened with K.
+struct SettingsScreen {
+    /// The position in `ACTIONS` of the highlighted action
+    selected: usize,
+    /// Whether the next key pressed is added to the highlighted action
+    rebinding: bool,
+}
+impl SettingsScreen {
+    fn new() -> SettingsScreen {
+        SettingsScreen { selected: 0, rebinding: false }
+    }
+    fn action(&self) -> Action {
+        ACTIONS[self.selected]
+    }
+    fn select(&mut self, offset: i32) {
+        let last = ACTIONS.len() as i32 - 1;
+        self.selected = (self.selected as i32 + offset).max(0).min(last) as usize;
+    }
+    fn title(&self) -> String {
+        if self.rebinding {
+            format!("Press a key for {} - Esc: cancel", self.action().name())
         } else {
-            line
-        };
-        if !line.is_empty() {
-            map_lines.push(line);
-        } else if line.is_empty() && !map_lines.is_empty() {
-            parsed_levels.push(Level::from_lines(map_lines)?);
-            map_lines = Vec::new();
+            "Keys - Enter: add a key, Delete: clear, R: defaults, Esc: close".to_string()
         }
     }
-    Ok(parsed_levels)
+    /// The rows on screen, with whether each is the highlighted one.
+    fn rows(&self, bindings: &Bindings) -> Vec<(String, bool)> {
+        let first = self.selected.saturating_sub(BROWSER_ROWS / 2).min(ACTIONS.len() - BROWSER_ROWS);
+        ACTIONS.iter().enumerate().skip(first).take(BROWSER_ROWS).map(|(row, &action)| {
+            let keys = bindings.keys(action);
+            let keys = if keys.is_empty() { "nothing".to_string() } else { keys.join(", ") };
+            (format!("{}: {}", action.name(), keys), row == self.selected)
+        }).collect()
+    }
+}
+
+/// The binding name of a gamepad button press, or of a stick being pushed one way.
+fn pad_input(event: &Event, sticks: &mut Sticks) -> Option<&'static str> {
+    match *event {
+        Event::ControllerButtonDown { button, .. } => pad::button_name(&button.string()),
+        Event::ControllerAxisMotion { which, axis, value, .. } => sticks.moved(which, &axis.string(), value),
+        _ => None,
+    }
 }
 
-fn floodfill<T: PartialEq + Copy>(map: &mut Vec<Vec<T>>, old: T, new: T, x: usize, y: usize) {
-    if map[y][x] == old {
-        map[y][x] = new;
+/// Every gamepad button, for seeing which are held down.
+const PAD_BUTTONS: [Button; 15] = [
+    Button::A, Button::B, Button::X, Button::Y, Button::Back, Button::Guide, Button::Start,
+    Button::LeftStick, Button::RightStick, Button::LeftShoulder, Button::RightShoulder,
+    Button::DPadUp, Button::DPadDown, Button::DPadLeft, Button::DPadRight,
+];
+
+/// The actions whose keys, buttons or sticks are held down right now.
+fn held_actions(event_pump: &EventPump, controllers: &[GameController], sticks: &Sticks, bindings: &Bindings) -> Vec<Action> {
+    let keys = event_pump.keyboard_state().pressed_scancodes()
+        .filter_map(Keycode::from_scancode)
+        .map(|key| key.name())
+        .collect::<Vec<_>>();
+    let buttons = controllers.iter()
+        .flat_map(|c| PAD_BUTTONS.iter().filter(move |&&b| c.button(b)))
+        .filter_map(|b| pad::button_name(&b.string()));
+    keys.iter().map(|k| &k[..])
+        .chain(buttons)
+        .chain(sticks.held())
+        .filter_map(|name| bindings.action(name))
+        .collect()
+}
+
+/// The key press a gamepad button or stick stands in for in the menus.
+fn menu_key_event(input: &str) -> Option<Event> {
+    let keycode = match input {
+        "Pad Up" | "Pad Stick Up" => Keycode::Up,
+        "Pad Down" | "Pad Stick Down" => Keycode::Down,
+        "Pad Left" | "Pad Stick Left" => Keycode::PageUp,
+        "Pad Right" | "Pad Stick Right" => Keycode::PageDown,
+        "Pad A" => Keycode::Return,
+        "Pad B" | "Pad Start" => Keycode::Escape,
+        _ => return None,
+    };
+    Some(Event::KeyDown { timestamp: 0, window_id: 0, keycode: Some(keycode), scancode: None, keymod: NOMOD, repeat: false })
+}
+
+/// Draws the minimap over the game: walls, goals, stars and pushers, with the part of the level on the screen outlined.
+fn draw_minimap(canvas: &mut Canvas<Window>, game: &Game, minimap: &Minimap) {
+    let to_rect = |(x, y, w, h): minimap::Area| rect!(x, y, w, h);
+    canvas.set_blend_mode(BlendMode::Blend);
+    canvas.set_draw_color(Color::RGBA(255, 255, 255, 190));
+    canvas.fill_rect(to_rect(minimap.area())).unwrap();
+    canvas.set_blend_mode(BlendMode::None);
+    let walls = game.level.map.iter().enumerate().flat_map(|(y, row)| {
+        row.iter().enumerate().filter(|t| *t.1 == Tile::Wall).map(move |(x, _)| Position::new(x, y))
+    }).map(|pos| to_rect(minimap.cell(pos))).collect::<Vec<_>>();
+    canvas.set_draw_color(Color::RGB(80, 80, 80));
+    canvas.fill_rects(&walls).unwrap();
+    // Colored goals and stars keep their colors, so it's clear which go together
+    let tint = |color: Option<u8>, plain: (u8, u8, u8)| {
+        let (r, g, b) = color.map(color_tint).unwrap_or(plain);
+        Color::RGB(r, g, b)
+    };
+    for goal in &game.state.goals {
+        canvas.set_draw_color(tint(goal.color, (210, 60, 60)));
+        let (x, y, w, h) = minimap.cell(goal.position);
+        // Goals are drawn hollow so a star on one still shows it's home
+        canvas.draw_rect(rect!(x, y, w, h)).unwrap();
+    }
+    for star in &game.state.stars {
+        canvas.set_draw_color(tint(star.color, (230, 170, 40)));
+        let (x, y, w, h) = minimap.cell(star.position);
+        canvas.fill_rect(rect!(x + 1, y + 1, (w as i32 - 2).max(1), (h as i32 - 2).max(1))).unwrap();
     }
-    if x > 0 && map[y][x-1] == old {
-        floodfill(map, old, new, x-1, y);
+    for (i, player) in game.state.players.iter().enumerate() {
+        let color = if i == game.state.active { Color::RGB(40, 110, 230) } else { Color::RGB(130, 170, 230) };
+        canvas.set_draw_color(color);
+        canvas.fill_rect(to_rect(minimap.cell(player.position))).unwrap();
     }
-    if x + 1 < map[y].len() && map[y][x+1] == old {
-        floodfill(map, old, new, x+1, y);
+    canvas.set_draw_color(Color::RGB(0, 0, 0));
+    canvas.draw_rect(to_rect(minimap.viewport(&game.camera))).unwrap();
+}
+
+/// The first key bound to `action`, for telling the player what to press.
+fn key_name(bindings: &Bindings, action: Action) -> String {
+    bindings.keys(action).first().cloned().unwrap_or_else(|| "?".to_string())
+}
+
+/// Shortens `text` to `max` characters, dropping the start with "..." if `from_start`, or the end otherwise.
+fn ellipsize(text: &str, max: usize, from_start: bool) -> String {
+    let count = text.chars().count();
+    if count <= max {
+        text.to_string()
+    } else if from_start {
+        format!("...{}", text.chars().skip(count - max + 3).collect::<String>())
+    } else {
+        format!("{}...", text.chars().take(max - 3).collect::<String>())
     }
-    if y > 0 && map[y-1][x] == old {
-        floodfill(map, old, new, x, y-1)
+}
+
+/// The first level of a file and how many levels it holds, or why it couldn't be read.
+type Preview = Result<(Level, usize), String>;
+
+/// The level file browser, opened with L.
+struct FileBrowser {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    /// Whether the recent files are listed instead of `dir`
+    recent: bool,
+    query: String,
+    /// The position in the filtered list of the highlighted entry
+    selected: usize,
+    preview: Option<(PathBuf, Preview)>,
+    error: Option<String>,
+}
+impl FileBrowser {
+    fn new(dir: &Path) -> FileBrowser {
+        let mut b = FileBrowser {
+            dir: dir.to_path_buf(), entries: Vec::new(), recent: false, query: String::new(), selected: 0, preview: None, error: None,
+        };
+        b.open_dir(dir);
+        b
     }
-    if y + 1 < map.len() && map[y+1][x] == old {
-        floodfill(map, old, new, x, y+1)
+    fn open_dir(&mut self, dir: &Path) {
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+        match files::list_dir(&dir) {
+            Ok(entries) => {
+                self.dir = dir;
+                self.entries = entries;
+                self.recent = false;
+                self.query.clear();
+                self.selected = 0;
+                self.error = None;
+            },
+            Err(e) => self.error = Some(e),
+        }
+    }
+    fn show_recent(&mut self, recent: &RecentFiles) {
+        self.entries = recent.entries();
+        self.recent = true;
+        self.query.clear();
+        self.selected = 0;
+        self.error = if self.entries.is_empty() { Some("No level files have been opened yet".to_string()) } else { None };
+    }
+    /// The entries that match the search.
+    fn shown(&self) -> Vec<&Entry> {
+        files::filter(&self.entries, &self.query)
+    }
+    fn selected_entry(&self) -> Option<Entry> {
+        self.shown().get(self.selected).map(|&e| e.clone())
+    }
+    fn select(&mut self, offset: i32) {
+        let last = self.shown().len() as i32 - 1;
+        self.selected = (self.selected as i32 + offset).min(last).max(0) as usize;
+    }
+    fn search(&mut self, query: String) {
+        self.query = query;
+        self.selected = 0;
+    }
+    fn title(&self) -> String {
+        if self.recent {
+            "Recent files".to_string()
+        } else {
+            format!("Files in {}", ellipsize(&self.dir.to_string_lossy(), 40, true))
+        }
+    }
+    /// The rows on screen, with whether each is the highlighted one.
+    fn rows(&self) -> Vec<(String, bool)> {
+        let shown = self.shown();
+        let first = self.selected.saturating_sub(BROWSER_ROWS / 2).min(shown.len().saturating_sub(BROWSER_ROWS));
+        shown.iter().enumerate().skip(first).take(BROWSER_ROWS).map(|(row, e)| {
+            (ellipsize(&e.name(), 24, false), row == self.selected)
+        }).collect()
+    }
+    /// Reads the highlighted file for the preview, unless it's already been read.
+    fn preview(&mut self) -> Option<&Preview> {
+        let path = match self.selected_entry() {
+            Some(Entry { path, kind: EntryKind::LevelFile }) => path,
+            _ => return None,
+        };
+        if self.preview.as_ref().is_none_or(|p| p.0 != path) {
+            let preview = load_level_file(&path.to_string_lossy()).and_then(|mut levels| {
+                if levels.is_empty() {
+                    Err("There are no levels in this file".to_string())
+                } else {
+                    let count = levels.len();
+                    Ok((levels.remove(0), count))
+                }
+            });
+            self.preview = Some((path, preview));
+        }
+        self.preview.as_ref().map(|p| &p.1)
     }
 }
 
-fn init_sdl(app_name: &str, width: u32, height: u32) -> Result<(Canvas<Window>, EventPump, Sdl2TtfContext), String> {
+/// The character a key adds to the file browser's search, if any.
+fn search_char(key: Keycode) -> Option<char> {
+    let code = key as i32;
+    if code < 0x80 {
+        Some(code as u8 as char).filter(|c| c.is_ascii_alphanumeric() || " .-_".contains(*c))
+    } else {
+        None
+    }
+}
+
+/// Draws a browser's title and rows, with the highlighted row in white. Returns the height of a line.
+fn draw_list(canvas: &mut Canvas<Window>, texture_creator: &TextureCreator<WindowContext>, font: &Font, title: &str, rows: &[(String, bool)]) -> u32 {
+    let title_texture = texture_creator.create_texture_from_surface(
+                            font.render(title)
+                                .blended(Color::RGB(0, 0, 0)).unwrap()
+                        ).unwrap();
+    let line_height = title_texture.query().height;
+    canvas.copy(&title_texture, None, Some(rect!(20, 20, title_texture.query().width, line_height))).unwrap();
+    for (row, &(ref text, selected)) in rows.iter().enumerate() {
+        let color = if selected { Color::RGB(255, 255, 255) } else { Color::RGB(0, 0, 0) };
+        let row_texture = texture_creator.create_texture_from_surface(
+                              font.render(text)
+                                  .blended(color).unwrap()
+                          ).unwrap();
+        let y = 40 + line_height * (row as u32 + 1);
+        canvas.copy(&row_texture, None, Some(rect!(40, y, row_texture.query().width, row_texture.query().height))).unwrap();
+    }
+    line_height
+}
+
+/// Writes `levels` to a level file, each with its "Difficulty" if it's been rated.
+fn export_levels(path: &str, levels: &[Level], ratings: &[Option<Difficulty>]) -> io::Result<()> {
+    let mut f = File::create(path)?;
+    for (level, rating) in levels.iter().zip(ratings) {
+        let mut level = level.clone();
+        if let Some(r) = *rating {
+            level.set_metadata("Difficulty", r.score.to_string());
+        }
+        // Every level ends with a blank line, the last one included
+        write!(f, "{}\n\n", level.text())?;
+    }
+    Ok(())
+}
+
+fn init_sdl(app_name: &str, width: u32, height: u32) -> Result<(Canvas<Window>, EventPump, Sdl2TtfContext, GameControllerSubsystem), String> {
     let sdl_context = sdl2::init()?;
     let _image_context = sdl2::image::init(INIT_PNG)?;
     let video_subsystem = sdl_context.video()?;
@@ -452,97 +594,685 @@ fn init_sdl(app_name: &str, width: u32, height: u32) -> Result<(Canvas<Window>,
     let canvas = window.into_canvas().build().map_err(|e| format!("{}", e))?;
     let event_pump = sdl_context.event_pump()?;
     let ttf_context = sdl2::ttf::init().map_err(|e| format!("{}", e))?;
-    Ok((canvas, event_pump, ttf_context))
+    // Pads already plugged in show up as ControllerDeviceAdded events, just like ones plugged in later
+    let controller_subsystem = sdl_context.game_controller()?;
+    Ok((canvas, event_pump, ttf_context, controller_subsystem))
+}
+
+#[cfg(feature = "embedded-assets")]
+fn load_spritesheet() -> Result<Surface<'static>, String> {
+    // The loaded surface borrows the RWops, so it's copied into one that doesn't
+    let rw = RWops::from_bytes(SPRITESHEET_BYTES)?;
+    let surface = rw.load()?;
+    surface.convert(&surface.pixel_format())
+}
+#[cfg(feature = "embedded-assets")]
+fn load_font(ttf_context: &Sdl2TtfContext, size: u16) -> Result<Font<'_, 'static>, String> {
+    ttf_context.load_font_from_rwops(RWops::from_bytes(FONT_BYTES)?, size)
+}
+/// Without embedded assets the pictures and the font are read from the resources directory.
+#[cfg(not(feature = "embedded-assets"))]
+fn load_spritesheet() -> Result<Surface<'static>, String> {
+    Surface::from_file(SPRITESHEET_PATH).map_err(|e| format!("{}: {}", SPRITESHEET_PATH, e))
+}
+#[cfg(not(feature = "embedded-assets"))]
+fn load_font(ttf_context: &Sdl2TtfContext, size: u16) -> Result<Font<'_, 'static>, String> {
+    ttf_context.load_font(FONT_PATH, size).map_err(|e| format!("{}: {}", FONT_PATH, e))
+}
+
+/// Asks where to save a level collection.
+#[cfg(feature = "native-dialogs")]
+fn save_levels_dialog() -> Option<String> {
+    tinyfiledialogs::save_file_dialog("Save level collection", "levels.txt")
+}
+#[cfg(not(feature = "native-dialogs"))]
+fn save_levels_dialog() -> Option<String> {
+    println!("This build has no file dialogs, so levels can't be saved from the game.");
+    None
 }
 
 fn main() {
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(code) = cli::run(&args) {
+        process::exit(code)
+    }
+    // With --watch the level file is reloaded whenever it's saved, for designing levels in a text editor
+    let watching = args.iter().any(|a| a == "--watch");
+    let args = args.into_iter().filter(|a| a != "--watch").collect::<Vec<_>>();
+    let watch_file = |path: &Path| if watching { Some(FileWatch::new(path.to_path_buf())) } else { None };
     // Load all of the game resources and start the game
     let mut level_number: i32 = 0;
-    let mut parsed_levels = load_levels(LEVELS).unwrap();
-    let mut game = Game::from_level(parsed_levels[level_number as usize].clone());
-    let (mut canvas, mut event_pump, ttf_context) = init_sdl("Sokoban", WIDTH, HEIGHT).unwrap();
-    let spritesheet_rw = RWops::from_bytes(&SPRITESHEET_BYTES).unwrap();
-    let spritesheet_surf = spritesheet_rw.load().unwrap();
-    let ttf_rw = RWops::from_bytes(&FONT_BYTES).unwrap();
+    let mut challenge = Challenge::Free;
+    let mut view = Transform::new(0, false);
+    // A level file given on the command line is played instead of the usual levels
+    let levels = match args.get(1) {
+        Some(path) => load_level_file(path),
+        None => default_levels(),
+    };
+    let mut parsed_levels = levels.unwrap_or_else(|e| {
+        eprintln!("Could not load the levels: {}", e);
+        process::exit(2)
+    });
+    // The file browser starts out where the level file came from
+    let mut level_dir = args.get(1)
+        .and_then(|path| Path::new(path).canonicalize().ok())
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .or_else(|| env::current_dir().ok())
+        .unwrap_or_else(|| PathBuf::from("."));
+    let mut watch = args.get(1).and_then(|path| watch_file(Path::new(path)));
+    // What went wrong reloading the level file, shown over the game until the file is fixed
+    let mut reload_error: Option<String> = None;
+    let mut game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+    let (mut canvas, mut event_pump, ttf_context, controller_subsystem) = init_sdl("Sokoban", WIDTH, HEIGHT).unwrap();
+    let mut controllers: Vec<GameController> = Vec::new();
+    let mut sticks = Sticks::new();
+    let spritesheet_surf = load_spritesheet().unwrap();
     let texture_creator = canvas.texture_creator();
-    let font = ttf_context.load_font_from_rwops(ttf_rw, 32).unwrap();
-    let ttf_rw = RWops::from_bytes(&FONT_BYTES).unwrap();
-    let big_font = ttf_context.load_font_from_rwops(ttf_rw, 64).unwrap();
+    let font = load_font(&ttf_context, 32).unwrap();
+    let big_font = load_font(&ttf_context, 64).unwrap();
     let mut clock = FpsClock::new(30);
+    let mut optimization: Option<Optimization> = None;
+    // A message along the bottom of the screen, from the optimizer or about loading and copying levels
+    let mut status_line: Option<String> = None;
+    let mut save_data = SaveData::load(save::default_path());
+    let mut browser: Option<Browser> = None;
+    let mut file_browser: Option<FileBrowser> = None;
+    let mut settings: Option<SettingsScreen> = None;
+    let mut bindings = Bindings::load(keys::default_path());
+    let mut recent_files = RecentFiles::load(files::default_recent_path());
+    let mut repeat = bindings.repeat();
+    let mut pan = Pan::new();
+    let mut follow = Follow::new(false);
+    let mut show_minimap = false;
+    let mut last_frame = Instant::now();
+    let mut shade_unreachable = false;
+    let mut ratings: Vec<Option<Difficulty>> = vec![None; parsed_levels.len()];
+    let mut rating: Option<Receiver<(usize, Difficulty)>> = None;
+    let mut generating: Option<Receiver<Result<Level, String>>> = None;
     'main: loop {
         for event in event_pump.poll_iter() {
             match event {
-                // The game exits when the user quits or hits the escape key
-                Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
-                    break 'main
+                Event::ControllerDeviceAdded { which, .. } => {
+                    match controller_subsystem.open(which as u32) {
+                        Ok(controller) => {
+                            println!("found {}", controller.name());
+                            controllers.push(controller);
+                        },
+                        Err(e) => println!("Could not open game controller {}: {}", which, e),
+                    }
+                },
+                Event::ControllerDeviceRemoved { which, .. } => {
+                    controllers.retain(|c| c.instance_id() != which);
+                    sticks.forget(which);
+                },
+                _ => (),
+            }
+            let pad_input = pad_input(&event, &mut sticks);
+            if let (Some(screen), Some(name)) = (settings.as_mut(), pad_input) {
+                if screen.rebinding {
+                    bindings.bind(screen.action(), name);
+                    screen.rebinding = false;
+                    continue
+                }
+            }
+            // The menus are worked with the keyboard keys, so pads stand in for those there
+            let in_menu = settings.is_some() || file_browser.is_some() || browser.is_some();
+            let event = match pad_input.and_then(menu_key_event) {
+                Some(key_event) if in_menu => key_event,
+                _ => event,
+            };
+            // Held keys are repeated by the game itself below, not by the system
+            let os_repeat = matches!(event, Event::KeyDown { repeat: true, .. });
+            // The name the key bindings know the key or button by
+            let input = match event {
+                Event::KeyDown { keycode: Some(key), .. } => Some(key.name()),
+                _ => pad_input.map(str::to_string),
+            };
+            if let Some(mut screen) = settings.take() {
+                // The settings screen's own keys can't be changed, so it can always be found again
+                match event {
+                    Event::Quit {..} => break 'main,
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } if screen.rebinding => screen.rebinding = false,
+                    Event::KeyDown { keycode: Some(key), .. } if screen.rebinding => {
+                        bindings.bind(screen.action(), &key.name());
+                        screen.rebinding = false;
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                        if let Err(e) = bindings.save() {
+                            println!("Could not save the keys: {}", e);
+                        }
+                        continue
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Up), .. } => screen.select(-1),
+                    Event::KeyDown { keycode: Some(Keycode::Down), .. } => screen.select(1),
+                    Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => screen.select(-(BROWSER_ROWS as i32)),
+                    Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => screen.select(BROWSER_ROWS as i32),
+                    Event::KeyDown { keycode: Some(Keycode::Return), .. } => screen.rebinding = true,
+                    Event::KeyDown { keycode: Some(Keycode::Delete), .. } => bindings.clear(screen.action()),
+                    Event::KeyDown { keycode: Some(Keycode::R), .. } => bindings.reset(),
+                    _ => (),
+                }
+                settings = Some(screen);
+                continue
+            }
+            if let Some(mut b) = file_browser.take() {
+                // So does the file browser, with the letters going into the search
+                match event {
+                    Event::Quit {..} => break 'main,
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => continue,
+                    Event::KeyDown { keycode: Some(Keycode::Up), .. } => b.select(-1),
+                    Event::KeyDown { keycode: Some(Keycode::Down), .. } => b.select(1),
+                    Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => b.select(-(BROWSER_ROWS as i32)),
+                    Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => b.select(BROWSER_ROWS as i32),
+                    Event::KeyDown { keycode: Some(Keycode::Tab), .. } => {
+                        if b.recent {
+                            let dir = b.dir.clone();
+                            b.open_dir(&dir);
+                        } else {
+                            b.show_recent(&recent_files);
+                        }
+                    },
+                    // Backspace takes back a letter of the search, or goes up a directory when there's none
+                    Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
+                        if !b.query.is_empty() {
+                            let mut query = b.query.clone();
+                            query.pop();
+                            b.search(query);
+                        } else if !b.recent {
+                            if let Some(parent) = b.dir.parent().map(Path::to_path_buf) {
+                                b.open_dir(&parent);
+                            }
+                        }
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                        match b.selected_entry() {
+                            Some(Entry { path, kind: EntryKind::LevelFile }) => {
+                                match load_level_file(&path.to_string_lossy()) {
+                                    Ok(ref levels) if levels.is_empty() => {
+                                        b.error = Some(format!("There are no levels in {}", path.display()));
+                                    },
+                                    Ok(levels) => {
+                                        println!("loading {}", path.display());
+                                        level_number = 0;
+                                        replace_levels(levels, &mut parsed_levels, &mut ratings, &mut rating, &mut optimization);
+                                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                                        if let Some(dir) = path.parent() {
+                                            level_dir = dir.to_path_buf();
+                                        }
+                                        watch = watch_file(&path);
+                                        reload_error = None;
+                                        recent_files.add(&path);
+                                        if let Err(e) = recent_files.save() {
+                                            println!("Could not save the recent files: {}", e);
+                                        }
+                                        continue
+                                    },
+                                    Err(e) => b.error = Some(e),
+                                }
+                            },
+                            Some(Entry { path, .. }) => b.open_dir(&path),
+                            None => (),
+                        }
+                    },
+                    Event::KeyDown { keycode: Some(key), .. } => {
+                        if let Some(c) = search_char(key) {
+                            let query = format!("{}{}", b.query, c);
+                            b.search(query);
+                        }
+                    },
+                    _ => (),
+                }
+                file_browser = Some(b);
+                continue
+            }
+            if let Some(mut b) = browser.take() {
+                // The level browser gets every key while it's open
+                match event {
+                    Event::Quit {..} => break 'main,
+                    Event::KeyDown { keycode: Some(Keycode::Escape), .. } => continue,
+                    Event::KeyDown { keycode: Some(Keycode::Up), .. } => b.select(-1),
+                    Event::KeyDown { keycode: Some(Keycode::Down), .. } => b.select(1),
+                    Event::KeyDown { keycode: Some(Keycode::PageUp), .. } => b.select(-(BROWSER_ROWS as i32)),
+                    Event::KeyDown { keycode: Some(Keycode::PageDown), .. } => b.select(BROWSER_ROWS as i32),
+                    Event::KeyDown { keycode: Some(Keycode::S), .. } => {
+                        b.sort = if b.sort == Sort::Number { Sort::Difficulty } else { Sort::Number };
+                        b.sort(&ratings);
+                    },
+                    Event::KeyDown { keycode: Some(Keycode::Return), .. } => {
+                        level_number = b.order[b.selected] as i32;
+                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                        continue
+                    },
+                    // Save the levels in the order they're listed, with their difficulty
+                    Event::KeyDown { keycode: Some(Keycode::E), .. } => {
+                        if let Some(path) = save_levels_dialog() {
+                            let levels = b.order.iter().map(|&i| parsed_levels[i].clone()).collect::<Vec<_>>();
+                            let level_ratings = b.order.iter().map(|&i| ratings[i]).collect::<Vec<_>>();
+                            match export_levels(&path, &levels, &level_ratings) {
+                                Ok(()) => println!("saved {} levels to {}", levels.len(), path),
+                                Err(e) => println!("Could not save {}: {}", path, e),
+                            }
+                        }
+                    },
+                    // The key that opened the browser closes it again
+                    Event::KeyDown { keycode: Some(key), .. } if bindings.action(&key.name()) == Some(Action::LevelBrowser) => continue,
+                    _ => (),
+                }
+                browser = Some(b);
+                continue
+            }
+            match event {
+                Event::Quit {..} => break 'main,
+                // Look at the part of the level the user clicked on the minimap
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. }
+                    if show_minimap && Minimap::new(&game).contains(x, y) => {
+                    Minimap::new(&game).look_at(&mut game.camera, x, y);
                 },
-                // Move to the next level if the user pressed N
-                Event::KeyDown { keycode: Some(Keycode::N), .. } => {
-                    let len = parsed_levels.len() as i32;
-                    level_number = (level_number + len + 1) % len;
-                    game = Game::from_level(parsed_levels[level_number as usize].clone());
+                // Walk to the square the user clicked on
+                Event::MouseButtonDown { mouse_btn: MouseButton::Left, x, y, .. } => {
+                    let (w, h) = game.level.topology.surface_size(game.level.width, game.level.height);
+                    let left = HALF_WIDTH as i32 + game.camera.x_offset - w as i32 / 2;
+                    let top = HALF_HEIGHT as i32 + game.camera.y_offset - h as i32 / 2;
+                    if let Some(pos) = game.level.cell_at(x - left, y - top) {
+                        game.walk_to(pos);
+                    }
                 },
-                // Move to the previous level if the user pressed B
-                Event::KeyDown { keycode: Some(Keycode::B), .. } => {
-                    let len = parsed_levels.len() as i32;
-                    level_number = (level_number + len - 1) % len;
-                    game = Game::from_level(parsed_levels[level_number as usize].clone());
+                // Play the levels copied to the clipboard if the user pressed Ctrl+V
+                Event::KeyDown { keycode: Some(Keycode::V), keymod, .. } if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                    let pasted = canvas.window().subsystem().clipboard().clipboard_text().and_then(|text| load_levels(&text));
+                    match pasted {
+                        Ok(ref levels) if levels.is_empty() => status_line = Some("There's no level on the clipboard".to_string()),
+                        Ok(levels) => {
+                            status_line = Some(format!("Pasted {} level{}", levels.len(), if levels.len() == 1 { "" } else { "s" }));
+                            level_number = 0;
+                            replace_levels(levels, &mut parsed_levels, &mut ratings, &mut rating, &mut optimization);
+                            game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                            watch = None;
+                            reload_error = None;
+                        },
+                        Err(e) => status_line = Some(format!("Could not paste the level: {}", e)),
+                    }
                 },
-                // Reset the level if the user pressed Backspace
-                Event::KeyDown { keycode: Some(Keycode::Backspace), .. } => {
-                    game = Game::from_level(parsed_levels[level_number as usize].clone());
+                // Copy the position as level text if the user pressed Ctrl+C, or the level as it starts with Ctrl+Shift+C
+                Event::KeyDown { keycode: Some(Keycode::C), keymod, .. } if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                    let (text, what) = if keymod.intersects(LSHIFTMOD | RSHIFTMOD) {
+                        (parsed_levels[level_number as usize].text(), "level")
+                    } else {
+                        (game.level.position_text(&game.state), "position")
+                    };
+                    status_line = Some(match canvas.window().subsystem().clipboard().set_clipboard_text(&text) {
+                        Ok(()) => format!("Copied the {} to the clipboard", what),
+                        Err(e) => format!("Could not copy the {}: {}", what, e),
+                    });
                 },
-                // Load a new level file if the user pressed L
-                Event::KeyDown { keycode: Some(Keycode::L), .. } => {
-                    if let Some(path) = tinyfiledialogs::open_file_dialog(
-                                            "Select a level file",
-                                            env::current_dir().unwrap().to_str().unwrap(), 
-                                            None) {
-                        println!("loading {}", path);
-                        let mut contents = String::new();
-                        let mut f = File::open(path).unwrap();
-                        f.read_to_string(&mut contents).unwrap();
-                        if let Ok(levels) = load_levels(&contents) {
+                // Play a level file dropped onto the window
+                Event::DropFile { ref filename, .. } => {
+                    match load_level_file(filename) {
+                        Ok(ref levels) if levels.is_empty() => status_line = Some(format!("There are no levels in {}", filename)),
+                        Ok(levels) => {
+                            println!("loading {}", filename);
+                            status_line = None;
                             level_number = 0;
-                            parsed_levels = levels;
-                            game = Game::from_level(parsed_levels[level_number as usize].clone());
+                            replace_levels(levels, &mut parsed_levels, &mut ratings, &mut rating, &mut optimization);
+                            game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                            watch = watch_file(Path::new(filename));
+                            reload_error = None;
+                            recent_files.add(Path::new(filename));
+                            if let Err(e) = recent_files.save() {
+                                println!("Could not save the recent files: {}", e);
+                            }
+                        },
+                        Err(e) => status_line = Some(format!("Could not load {}: {}", filename, e)),
+                    }
+                },
+                _ => match input.and_then(|name| bindings.action(&name)) {
+                    Some(Action::Quit) => break 'main,
+                    Some(Action::NextLevel) => {
+                        let len = parsed_levels.len() as i32;
+                        level_number = (level_number + len + 1) % len;
+                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                    },
+                    Some(Action::PreviousLevel) => {
+                        let len = parsed_levels.len() as i32;
+                        level_number = (level_number + len - 1) % len;
+                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                    },
+                    Some(Action::Reset) => {
+                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                    },
+                    // Restart the level in the next challenge mode
+                    Some(Action::NextMode) => {
+                        challenge = challenge.next();
+                        game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                    },
+                    // Make up a new level in the background, and play it when it's ready
+                    Some(Action::Generate) if generating.is_none() => {
+                        generating = Some(generator::spawn(generator::DEFAULT_SETTINGS, cli::clock_seed()));
+                        status_line = Some("Generating a level...".to_string());
+                    },
+                    // Shade the floor the pusher can't walk to
+                    Some(Action::ShadeUnreachable) => {
+                        shade_unreachable = !shade_unreachable;
+                    },
+                    Some(Action::LevelBrowser) => {
+                        browser = Some(Browser::new(parsed_levels.len(), level_number as usize));
+                    },
+                    Some(Action::OpenFile) => {
+                        file_browser = Some(FileBrowser::new(&level_dir));
+                    },
+                    Some(Action::Settings) => {
+                        settings = Some(SettingsScreen::new());
+                    },
+                    // Turn the level a quarter clockwise, or mirror it
+                    Some(action @ Action::TurnLevel) | Some(action @ Action::MirrorLevel) => {
+                        let t = if action == Action::TurnLevel { Transform::new(1, false) } else { Transform::new(0, true) };
+                        // Hexoban levels can't be turned, and the next levels shouldn't come up turned because of it
+                        match t.level(&game.level) {
+                            Ok(_) => {
+                                view = view.then(t);
+                                game = game.transformed(t);
+                            },
+                            Err(e) => status_line = Some(e),
+                        }
+                    },
+                    // Look for a shorter version of the level's known solution
+                    Some(action @ Action::OptimizeMoves) | Some(action @ Action::OptimizePushes) => {
+                        let metric = if action == Action::OptimizeMoves { Metric::Moves } else { Metric::Pushes };
+                        let level = &parsed_levels[level_number as usize];
+                        // Without a "Solution" in the level, start from the best one saved for it
+                        let saved = save_data.best(canonical::hash(level))
+                                             .filter(|best| !best.solution.contains(','))
+                                             .map(|best| canonical::from_canonical(level, &best.solution));
+                        match metadata_value(&level.metadata, "Solution").map(str::to_string).or(saved) {
+                            Some(solution) => {
+                                optimization = Some(Optimization::start(level_number, level, solution, metric));
+                            },
+                            None => {
+                                status_line = Some(format!("Level {} has no solution to optimize yet", level_number+1));
+                            },
+                        }
+                    },
+                    Some(Action::FollowPusher) => {
+                        follow.toggle();
+                        status_line = Some(if follow.on { "The camera follows the pusher" } else { "The camera stays put" }.to_string());
+                    },
+                    Some(Action::CenterCamera) => follow.center(),
+                    Some(Action::Minimap) => {
+                        show_minimap = !show_minimap;
+                    },
+                    // The camera pans for as long as its keys are held, below
+                    Some(action) if hold::is_camera(action) => (),
+                    Some(action) if hold::is_move(action) && os_repeat => (),
+                    // Moving and undoing are up to the game
+                    Some(action) => {
+                        game.act(action);
+                        repeat.press(action);
+                    },
+                    None => (),
+                },
+            }
+        }
+        // Pan the camera and repeat moves for the keys held down, and follow the pusher, by the time since the last frame
+        let now = Instant::now();
+        let elapsed = now - last_frame;
+        last_frame = now;
+        if settings.is_none() && file_browser.is_none() && browser.is_none() {
+            let held = held_actions(&event_pump, &controllers, &sticks, &bindings);
+            pan.update(&mut game.camera, &held, elapsed);
+            follow.update(&mut game, elapsed);
+            if let Some((action, times)) = repeat.update(&held, elapsed) {
+                for _ in 0..times {
+                    game.act(action);
+                }
+            }
+        }
+        // Reload the level file if it's been saved since it was loaded, staying on the same level.
+        // Not while the level browser is open though, since it lists the old levels.
+        let changed = match watch {
+            Some(ref mut w) if browser.is_none() => if w.poll() { Some(w.path.clone()) } else { None },
+            _ => None,
+        };
+        if let Some(path) = changed {
+            match load_level_file(&path.to_string_lossy()) {
+                Ok(ref levels) if levels.is_empty() => reload_error = Some(format!("There are no levels in {}", path.display())),
+                Ok(levels) => {
+                    println!("reloading {}", path.display());
+                    reload_error = None;
+                    level_number = level_number.min(levels.len() as i32 - 1);
+                    replace_levels(levels, &mut parsed_levels, &mut ratings, &mut rating, &mut optimization);
+                    let camera = game.camera.clone();
+                    game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                    game.camera.pan_like(&camera);
+                },
+                Err(e) => reload_error = Some(e),
+            }
+        }
+        // Play the generated level once it's ready
+        let generated = match generating {
+            Some(ref receiver) => match receiver.try_recv() {
+                Ok(result) => Some(result),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => Some(Err("the generator stopped".to_string())),
+            },
+            None => None,
+        };
+        if let Some(result) = generated {
+            generating = None;
+            match result {
+                Ok(level) => {
+                    status_line = None;
+                    parsed_levels.push(level);
+                    ratings.push(None);
+                    level_number = parsed_levels.len() as i32 - 1;
+                    game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                },
+                Err(e) => status_line = Some(format!("Could not generate a level: {}", e)),
+            }
+        }
+        // See how the optimizer is getting on
+        let mut finished = false;
+        if let Some(ref opt) = optimization {
+            while let Ok(progress) = opt.progress.try_recv() {
+                let n = opt.level_number;
+                status_line = Some(match progress {
+                    Progress::Searched(searched) => {
+                        format!("Optimizing level {} for {}: {} positions searched", n+1, opt.metric.name(), searched)
+                    },
+                    Progress::Done(Ok(solution)) => {
+                        finished = true;
+                        println!("Level {} optimized solution: {}", n+1, solution.lurd);
+                        let level = &mut parsed_levels[n as usize];
+                        let improved = level.par() != Some((solution.moves, solution.pushes));
+                        level.set_metadata("Solution", solution.lurd);
+                        ratings[n as usize] = None;
+                        if improved {
+                            format!("Level {}: found {} moves, {} pushes", n+1, solution.moves, solution.pushes)
                         } else {
-                            tinyfiledialogs::message_box_ok(
-                                "Error!", 
-                                "Could not load level file", 
-                                tinyfiledialogs::MessageBoxIcon::Error
-                            );
+                            format!("Level {}: no shorter solution found", n+1)
                         }
-                    }
+                    },
+                    Progress::Done(Err(e)) => {
+                        finished = true;
+                        format!("Could not optimize level {}: {}", n+1, e)
+                    },
+                });
+            }
+        }
+        if finished {
+            optimization = None;
+        }
+        // Rate the levels in the background while the level browser is open
+        if browser.is_some() && rating.is_none() && ratings.contains(&None) {
+            let unrated = ratings.iter().enumerate().filter(|r| r.1.is_none()).map(|r| (r.0, parsed_levels[r.0].clone())).collect();
+            rating = Some(difficulty::spawn(unrated));
+        }
+        let mut rated = false;
+        if let Some(ref receiver) = rating {
+            loop {
+                match receiver.try_recv() {
+                    Ok((i, r)) => ratings[i] = Some(r),
+                    Err(TryRecvError::Empty) => break,
+                    // The rater is done, so see if anything else needs rating on the next frame
+                    Err(TryRecvError::Disconnected) => {
+                        rated = true;
+                        break
+                    },
+                }
+            }
+        }
+        if rated {
+            rating = None;
+        }
+        if let Some(ref screen) = settings {
+            canvas.set_draw_color(BACKGROUND_COLOR);
+            canvas.clear();
+            draw_list(&mut canvas, &texture_creator, &font, &screen.title(), &screen.rows(&bindings));
+            canvas.present();
+            clock.tick();
+            continue
+        }
+        if let Some(ref mut b) = file_browser {
+            canvas.set_draw_color(BACKGROUND_COLOR);
+            canvas.clear();
+            let line_height = draw_list(&mut canvas, &texture_creator, &font, &b.title(), &b.rows());
+            // The first level of the highlighted file, shrunk to fit next to the list
+            let preview_box = rect!(500, 40 + line_height, 380, line_height * BROWSER_ROWS as u32);
+            let caption = match b.preview() {
+                Some(Ok((level, count))) => {
+                    let level_surf = Game::from_level(level.clone()).render_to_surface(&spritesheet_surf, false);
+                    let scale = (preview_box.width() as f64 / level_surf.width() as f64)
+                                    .min(preview_box.height() as f64 / level_surf.height() as f64)
+                                    .min(1.0);
+                    let (w, h) = ((level_surf.width() as f64 * scale) as u32, (level_surf.height() as f64 * scale) as u32);
+                    let preview_texture = texture_creator.create_texture_from_surface(level_surf).unwrap();
+                    canvas.copy(&preview_texture, None, Some(Rect::from_center(preview_box.center(), w, h))).unwrap();
+                    Some(if *count == 1 { "1 level".to_string() } else { format!("{} levels", count) })
                 },
-                // Let the game object handle the event
-                event => game.step(&event),
+                Some(Err(e)) => Some(ellipsize(e, 24, false)),
+                None => None,
+            };
+            if let Some(caption) = caption {
+                let caption_texture = texture_creator.create_texture_from_surface(
+                                          font.render(&caption)
+                                              .blended(Color::RGB(0, 0, 0)).unwrap()
+                                      ).unwrap();
+                let (w, h) = (caption_texture.query().width, caption_texture.query().height);
+                canvas.copy(&caption_texture, None, Some(rect!(preview_box.x(), preview_box.bottom() + 10, w, h))).unwrap();
             }
+            // The search and the keys go along the bottom, or what went wrong if anything did
+            let footer = match b.error {
+                Some(ref e) => (e.clone(), Color::RGB(140, 0, 0)),
+                None if !b.query.is_empty() => (format!("Search: {}", b.query), Color::RGB(0, 0, 0)),
+                None => ("Type to search - Enter: open, Tab: recent, Esc: close".to_string(), Color::RGB(0, 0, 0)),
+            };
+            let footer_texture = texture_creator.create_texture_from_surface(
+                                     font.render(&footer.0)
+                                         .blended(footer.1).unwrap()
+                                 ).unwrap();
+            let (w, h) = (footer_texture.query().width, footer_texture.query().height);
+            canvas.copy(&footer_texture, None, Some(rect!(20, HEIGHT - h - 20, w, h))).unwrap();
+            canvas.present();
+            clock.tick();
+            continue
+        }
+        if let Some(ref mut b) = browser {
+            if b.sort == Sort::Difficulty {
+                b.sort(&ratings);
+            }
+            canvas.set_draw_color(BACKGROUND_COLOR);
+            canvas.clear();
+            draw_list(&mut canvas, &texture_creator, &font, &b.title(), &b.rows(&parsed_levels, &ratings));
+            canvas.present();
+            clock.tick();
+            continue
         }
         // Render the new game state
-        let level_surf = game.render_to_surface(&spritesheet_surf);
+        let level_surf = game.render_to_surface(&spritesheet_surf, shade_unreachable);
         let mut rect = level_surf.rect();
         rect.center_on(Point::new(HALF_WIDTH as i32 + game.camera.x_offset, HALF_HEIGHT as i32 + game.camera.y_offset));
         let level_texture = texture_creator.create_texture_from_surface(level_surf).unwrap();
         let text_texture = texture_creator.create_texture_from_surface(
-                                font.render(&format!("Level {}", level_number+1))
+                                font.render(&hud_text(&game, level_number))
+                                    .blended(Color::RGB(0, 0, 0)).unwrap()
+                            ).unwrap();
+        let status_texture = texture_creator.create_texture_from_surface(
+                                font.render(&game.status_text())
                                     .blended(Color::RGB(0, 0, 0)).unwrap()
                             ).unwrap();
-        canvas.set_draw_color(*BACKGROUND_COLOR);
+        let text_height = text_texture.query().height;
+        canvas.set_draw_color(BACKGROUND_COLOR);
         canvas.clear();
         canvas.copy(&level_texture, None, Some(rect)).expect("Render failed");
-        canvas.copy(&text_texture, None, Some(rect!(20, 20, text_texture.query().width, text_texture.query().height))).unwrap();
+        canvas.copy(&text_texture, None, Some(rect!(20, 20, text_texture.query().width, text_height))).unwrap();
+        canvas.copy(&status_texture, None, Some(rect!(20, 20 + text_height, status_texture.query().width, status_texture.query().height))).unwrap();
+        if show_minimap {
+            draw_minimap(&mut canvas, &game, &Minimap::new(&game));
+        }
+        if let Some(ref status) = status_line {
+            let status_line_texture = texture_creator.create_texture_from_surface(
+                                        font.render(status)
+                                            .blended(Color::RGB(0, 0, 0)).unwrap()
+                                    ).unwrap();
+            let (w, h) = (status_line_texture.query().width, status_line_texture.query().height);
+            canvas.copy(&status_line_texture, None, Some(rect!(20, HEIGHT - h - 20, w, h))).unwrap();
+        }
+        if let Some(ref e) = reload_error {
+            // The old levels stay playable underneath until the file is fixed
+            let error_texture = texture_creator.create_texture_from_surface(
+                                    font.render(&ellipsize(&format!("Could not reload: {}", e), 60, false))
+                                        .blended(Color::RGB(255, 255, 255)).unwrap()
+                                ).unwrap();
+            let (w, h) = (error_texture.query().width, error_texture.query().height);
+            let backdrop = Rect::from_center(Point::new(HALF_WIDTH as i32, HALF_HEIGHT as i32), w + 40, h + 20);
+            canvas.set_blend_mode(BlendMode::Blend);
+            canvas.set_draw_color(Color::RGBA(100, 0, 0, 200));
+            canvas.fill_rect(backdrop).unwrap();
+            canvas.set_blend_mode(BlendMode::None);
+            canvas.copy(&error_texture, None, Some(Rect::from_center(backdrop.center(), w, h))).unwrap();
+        }
         canvas.present();
-        if game.solved() {
+        let solved = game.solved();
+        if solved || game.failed() {
+            let (message, prompt) = if solved && game.state.players.len() == 1 {
+                let keys = format!("{}/{}", key_name(&bindings, Action::OptimizeMoves), key_name(&bindings, Action::OptimizePushes));
+                ("You solved it!", format!("Hit any key to move on, or {} to optimize", keys))
+            } else if solved {
+                ("You solved it!", "Hit any key to move on".to_string())
+            } else {
+                (game.failure_text(), "Hit any key to try again".to_string())
+            };
             let you_win_texture = texture_creator.create_texture_from_surface(
-                                big_font.render("You solved it!")
+                                big_font.render(message)
                                     .blended(Color::RGB(0, 0, 0)).unwrap()
                             ).unwrap();
             let you_win_rect = Rect::from_center(Point::new(HALF_WIDTH as i32, (HALF_HEIGHT - you_win_texture.query().height) as i32), 
                                                 you_win_texture.query().width, 
                                                 you_win_texture.query().height);
+            let mut result = game.result_text();
+            if solved {
+                // Keep the best solution for the level, under a key that finds it again in any level pack.
+                // The solution is turned to fit the canonical form, so it plays on any turned copy of the level too.
+                let hash = canonical::hash(&game.level);
+                let solution = canonical::to_canonical(&game.level, &game.solutions().join(","));
+                let record = Record { moves: game.state.steps, pushes: game.state.pushes, solution };
+                if save_data.record(hash, record) {
+                    result.push_str(" - new best!");
+                    if let Err(e) = save_data.save() {
+                        println!("Could not save solutions: {}", e);
+                    }
+                } else if let Some(best) = save_data.best(hash) {
+                    result.push_str(&format!(" (best {}/{})", best.moves, best.pushes));
+                }
+            }
+            let result_texture = texture_creator.create_texture_from_surface(
+                                        font.render(&result)
+                                            .blended(Color::RGB(0, 0, 0)).unwrap()
+                                    ).unwrap();
+            let result_rect = Rect::from_center(Point::new(HALF_WIDTH as i32, HALF_HEIGHT as i32), 
+                                                result_texture.query().width, 
+                                                result_texture.query().height);
             let hit_key_texture = texture_creator.create_texture_from_surface(
-                                        font.render("Hit any key to move on")
+                                        font.render(&prompt)
                                             .blended(Color::RGB(0, 0, 0)).unwrap()
                                     ).unwrap();
             let hit_key_rect = Rect::from_center(Point::new(HALF_WIDTH as i32, (HALF_HEIGHT + you_win_texture.query().height) as i32), 
@@ -554,16 +1284,31 @@ fn main() {
             canvas.copy(&level_texture, None, Some(rect)).expect("Render failed");
             canvas.copy(&text_texture, None, Some(rect!(20, 20, text_texture.query().width, text_texture.query().height))).expect("Render failed");
             canvas.copy(&you_win_texture, None, Some(you_win_rect)).expect("Render failed");
+            canvas.copy(&result_texture, None, Some(result_rect)).expect("Render failed");
             canvas.copy(&hit_key_texture, None, Some(hit_key_rect)).expect("Render failed");
             canvas.present();
             // Loop until the user presses a key to move on.
             'you_win: loop {
                 for event in event_pump.poll_iter() {
-                    match event {
-                        Event::Quit {..} | Event::KeyDown { keycode: Some(Keycode::Escape), .. } => {
+                    let pad_input = pad_input(&event, &mut sticks);
+                    let action = match event {
+                        Event::KeyDown { keycode: Some(key), .. } => bindings.action(&key.name()),
+                        _ => pad_input.and_then(|name| bindings.action(name)),
+                    };
+                    match (event, action) {
+                        (Event::Quit {..}, _) | (_, Some(Action::Quit)) => {
                             break 'main
                         },
-                        Event::KeyDown { .. } => {
+                        // Optimize the solution the player just found for moves or pushes
+                        (_, Some(action @ Action::OptimizeMoves)) | (_, Some(action @ Action::OptimizePushes))
+                            if solved && game.state.players.len() == 1 => {
+                            let metric = if action == Action::OptimizeMoves { Metric::Moves } else { Metric::Pushes };
+                            // Optimize on the level as it was loaded, so the result fits the level's "Solution"
+                            let solution = game.view.inverse().lurd(&game.solutions()[0]);
+                            optimization = Some(Optimization::start(level_number, &parsed_levels[level_number as usize], solution, metric));
+                            break 'you_win
+                        },
+                        (Event::KeyDown { .. }, _) | (Event::ControllerButtonDown { .. }, _) => {
                             break 'you_win
                         },
                         _ => ()
@@ -571,10 +1316,18 @@ fn main() {
                 }
                 clock.tick()
             }
+            if !solved {
+                // Try the level again
+                game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
+                continue
+            }
+            for (i, solution) in game.solutions().iter().enumerate() {
+                println!("Level {} pusher {} solution: {}", level_number+1, i+1, solution);
+            }
             // Move to the next level
             let len = parsed_levels.len() as i32;
             level_number = (level_number + len + 1) % len;
-            game = Game::from_level(parsed_levels[level_number as usize].clone());
+            game = Game::from_level(parsed_levels[level_number as usize].clone()).with_challenge(challenge).transformed(view);
         }
         clock.tick();
     }
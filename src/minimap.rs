@@ -0,0 +1,76 @@
+//! A small map of the whole level in the top right corner of the screen, for finding the way around
+//! levels too big to see at once. This works out where things go on it; the drawing is up to the front end.
+
+use {Camera, Game, Position, Topology, HALF_HEIGHT, HALF_WIDTH, TILE_HEIGHT, TILE_WIDTH};
+
+/// The most room the minimap takes up, in pixels. Levels keep their shape, so one side is usually shorter.
+pub const MAX_SIZE: (u32, u32) = (240, 180);
+/// How far the minimap is from the edges of the screen.
+pub const MARGIN: i32 = 20;
+
+/// A rectangle on the screen: left, top, width and height.
+pub type Area = (i32, i32, u32, u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Minimap {
+    left: i32,
+    top: i32,
+    /// Minimap pixels for each pixel of the rendered level
+    scale: f64,
+    /// The size of the rendered level, in pixels
+    level_size: (u32, u32),
+    topology: Topology,
+}
+impl Minimap {
+    pub fn new(game: &Game) -> Minimap {
+        let level = &game.level;
+        let (w, h) = level.topology.surface_size(level.width, level.height);
+        let scale = (MAX_SIZE.0 as f64 / w as f64).min(MAX_SIZE.1 as f64 / h as f64);
+        let width = (w as f64 * scale) as i32;
+        Minimap {
+            left: 2 * HALF_WIDTH as i32 - MARGIN - width,
+            top: MARGIN,
+            scale,
+            level_size: (w, h),
+            topology: level.topology,
+        }
+    }
+    /// Where the whole minimap is.
+    pub fn area(&self) -> Area {
+        let (w, h) = self.level_size;
+        (self.left, self.top, (w as f64 * self.scale) as u32, (h as f64 * self.scale) as u32)
+    }
+    /// Where the cell at `pos` is drawn, at least a pixel across so nothing goes missing on the biggest levels.
+    pub fn cell(&self, pos: Position) -> Area {
+        let (x, y) = self.topology.tile_origin(pos.x, pos.y);
+        let size = |tile: u32| ((tile as f64 * self.scale).ceil() as u32).max(1);
+        (self.left + (x as f64 * self.scale) as i32, self.top + (y as f64 * self.scale) as i32,
+         size(TILE_WIDTH), size(TILE_HEIGHT))
+    }
+    /// The part of the level on the screen with `camera`, as far as it's inside the minimap.
+    pub fn viewport(&self, camera: &Camera) -> Area {
+        let (w, h) = self.level_size;
+        // The screen's left edge is this far into the level, and likewise for the top
+        let x = w as i32 / 2 - camera.x_offset - HALF_WIDTH as i32;
+        let y = h as i32 / 2 - camera.y_offset - HALF_HEIGHT as i32;
+        let clip = |start: i32, length: u32, size: u32| {
+            let end = (start + length as i32).min(size as i32);
+            let start = start.max(0);
+            ((start as f64 * self.scale) as i32, ((end - start).max(0) as f64 * self.scale) as u32)
+        };
+        let (left, width) = clip(x, 2 * HALF_WIDTH, w);
+        let (top, height) = clip(y, 2 * HALF_HEIGHT, h);
+        (self.left + left, self.top + top, width, height)
+    }
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        let (left, top, w, h) = self.area();
+        x >= left && y >= top && x < left + w as i32 && y < top + h as i32
+    }
+    /// Points `camera` at the spot on the level under (x, y) on the minimap, as near as it can pan.
+    pub fn look_at(&self, camera: &mut Camera, x: i32, y: i32) {
+        let (w, h) = self.level_size;
+        let level_x = ((x - self.left) as f64 / self.scale) as i32;
+        let level_y = ((y - self.top) as f64 / self.scale) as i32;
+        camera.pan(w as i32 / 2 - level_x - camera.x_offset, h as i32 / 2 - level_y - camera.y_offset);
+    }
+}
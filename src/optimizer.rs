@@ -0,0 +1,215 @@
+//! Shortens an existing solution by searching the positions close to the ones it passes through,
+//! like the vicinity search in YASO and Sokoban YASC.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread;
+
+use {Direction, Game, GameState, Level, Position, Star};
+use bitboard::Board;
+use verifier;
+
+/// Give up after looking at this many positions.
+const MAX_POSITIONS: usize = 500_000;
+/// How often the search reports its progress, in positions.
+const PROGRESS_INTERVAL: usize = 5_000;
+
+/// What the optimizer tries to minimize first. Ties are broken by the other count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    Moves,
+    Pushes,
+}
+impl Metric {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Metric::Moves => "moves",
+            Metric::Pushes => "pushes",
+        }
+    }
+}
+
+/// How far from the original solution the search may wander.
+/// A position is searched if, compared to some position on the original solution,
+/// at most `stars` stars are somewhere else, and each of those is at most `radius` steps away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vicinity {
+    pub stars: usize,
+    pub radius: usize,
+}
+pub const DEFAULT_VICINITY: Vicinity = Vicinity { stars: 2, radius: 4 };
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Solution {
+    pub lurd: String,
+    pub moves: usize,
+    pub pushes: usize,
+}
+
+/// Messages sent back by an optimizer running in the background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Progress {
+    /// The number of positions searched so far
+    Searched(usize),
+    /// The best solution found, which is the original one if nothing shorter turned up
+    Done(Result<Solution, String>),
+}
+
+/// The stars of `state` in order, so layouts that only differ in the order of the stars are the same.
+fn sorted_stars(state: &GameState) -> Vec<Star> {
+    let mut stars = state.stars.clone();
+    stars.sort();
+    stars
+}
+
+struct Node {
+    state: GameState,
+    /// The node this one was reached from, and the move that got here
+    parent: Option<(usize, Direction, bool)>,
+}
+
+/// Replays `solution` and returns every position it passes through, starting with the level's start.
+fn replay(level: &Level, solution: &str) -> Result<Vec<GameState>, String> {
+    let moves = verifier::parse(solution).map_err(|i| format!("Character {} of the solution is not a LURD move", i + 1))?;
+    let mut game = Game::from_level(level.clone());
+    let mut states = vec![game.state.clone()];
+    for (i, dir, push) in moves {
+        // Held to the same rules as --verify, so the two agree on what a solution is
+        if !verifier::step(&mut game, dir, push) {
+            return Err(format!("Character {} of the solution is not a legal move", i + 1));
+        }
+        states.push(game.state.clone());
+    }
+    if !game.solved() {
+        return Err("The solution doesn't solve the level".to_string());
+    }
+    Ok(states)
+}
+
+fn distance(a: Position, b: Position) -> usize {
+    ((a.x as i32 - b.x as i32).abs() + (a.y as i32 - b.y as i32).abs()) as usize
+}
+
+/// Whether `stars` is in the vicinity of one of the star layouts the original solution passes through.
+fn in_vicinity(stars: &[Star], layouts: &[Vec<Star>], vicinity: Vicinity) -> bool {
+    layouts.iter().any(|layout| {
+        let moved = stars.iter().filter(|s| !layout.contains(s));
+        let left = layout.iter().filter(|s| !stars.contains(s)).collect::<Vec<_>>();
+        moved.clone().count() <= vicinity.stars
+            && moved.into_iter().all(|s| left.iter().any(|l| distance(l.position, s.position) <= vicinity.radius))
+    })
+}
+
+fn cost(metric: Metric, moves: usize, pushes: usize) -> (usize, usize) {
+    match metric {
+        Metric::Moves => (moves, pushes),
+        Metric::Pushes => (pushes, moves),
+    }
+}
+
+/// Looks for a shorter solution than `solution` to `level`.
+/// `progress` is called with the number of positions searched every now and then,
+/// and the search is cancelled if it returns false.
+pub fn optimize<F>(level: &Level,
+                   solution: &str,
+                   metric: Metric,
+                   vicinity: Vicinity,
+                   mut progress: F) -> Result<Solution, String>
+    where F: FnMut(usize) -> bool
+{
+    if level.start_state.players.len() != 1 {
+        return Err("Only levels with a single pusher can be optimized".to_string());
+    }
+    let path = replay(level, solution)?;
+    let original = {
+        // Write the original out again from the replay, so it's in the same form as the solutions we find
+        let mut game = Game::from_level(level.clone());
+        let mut lurd = String::new();
+        for state in &path[1..] {
+            let dir = state.player().direction;
+            lurd.push(dir.lurd(state.pushes != game.state.pushes));
+            game.state = state.clone();
+        }
+        Solution { lurd, moves: game.state.steps, pushes: game.state.pushes }
+    };
+    let layouts = path.iter()
+                      .map(sorted_stars)
+                      .collect::<HashSet<_>>()
+                      .into_iter()
+                      .collect::<Vec<_>>();
+
+    let board = Board::new(level);
+    let mut game = Game::from_level(level.clone());
+    let mut nodes = vec![Node { state: game.state.clone(), parent: None }];
+    let mut best = HashMap::new();
+    best.insert(board.compact(&game.state), (0, 0));
+    let mut queue = BinaryHeap::new();
+    queue.push(Reverse((0, 0, 0)));
+    while let Some(Reverse((first, second, index))) = queue.pop() {
+        let key = board.compact(&nodes[index].state);
+        if best.get(&key).is_some_and(|&c| c < (first, second)) {
+            // We already found a cheaper way here
+            continue
+        }
+        game.state = nodes[index].state.clone();
+        if game.solved() {
+            let mut moves = Vec::new();
+            let mut i = index;
+            while let Some((parent, dir, push)) = nodes[i].parent {
+                moves.push(dir.lurd(push));
+                i = parent;
+            }
+            let state = &nodes[index].state;
+            let found = Solution {
+                lurd: moves.into_iter().rev().collect(),
+                moves: state.steps,
+                pushes: state.pushes,
+            };
+            if cost(metric, found.moves, found.pushes) < cost(metric, original.moves, original.pushes) {
+                return Ok(found)
+            }
+            return Ok(original)
+        }
+        if nodes.len() >= MAX_POSITIONS {
+            break
+        }
+        for &dir in level.topology.directions() {
+            game.state = nodes[index].state.clone();
+            game.make_move(dir);
+            if game.state.steps == nodes[index].state.steps {
+                continue
+            }
+            let push = game.state.pushes != nodes[index].state.pushes;
+            if !board.fits(&game.state) || push && !in_vicinity(&sorted_stars(&game.state), &layouts, vicinity) {
+                continue
+            }
+            let key = board.compact(&game.state);
+            let c = cost(metric, game.state.steps, game.state.pushes);
+            if best.get(&key).is_some_and(|&b| b <= c) {
+                continue
+            }
+            best.insert(key, c);
+            nodes.push(Node { state: game.state.clone(), parent: Some((index, dir, push)) });
+            queue.push(Reverse((c.0, c.1, nodes.len() - 1)));
+            if nodes.len() % PROGRESS_INTERVAL == 0 && !progress(nodes.len()) {
+                return Err("Cancelled".to_string())
+            }
+        }
+    }
+    Ok(original)
+}
+
+/// Runs `optimize` on another thread, so the game can keep going while it searches.
+/// Dropping the receiver cancels the search.
+pub fn spawn(level: Level, solution: String, metric: Metric) -> Receiver<Progress> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        let result = optimize(&level, &solution, metric, DEFAULT_VICINITY, |searched| {
+            sender.send(Progress::Searched(searched)).is_ok()
+        });
+        // Nobody is listening anymore if this fails, which is fine
+        let _ = sender.send(Progress::Done(result));
+    });
+    receiver
+}
@@ -0,0 +1,97 @@
+//! Gamepads, with their buttons and sticks given names the key bindings can use like key names.
+//! It works from the names SDL's game controller layer gives buttons and axes, like "a" or "leftx",
+//! so it doesn't need SDL itself.
+
+use std::collections::HashMap;
+
+use Direction;
+
+/// How far a stick has to lean, out of 32767, to count as pushed.
+pub const STICK_PUSH: i32 = 16384;
+/// How far back toward the middle a stick has to come before it can be pushed again,
+/// so a stick held right at `STICK_PUSH` doesn't flicker.
+pub const STICK_RELEASE: i32 = 8192;
+
+/// The binding name for a button, from SDL's name for it.
+pub fn button_name(button: &str) -> Option<&'static str> {
+    Some(match button {
+        "a" => "Pad A",
+        "b" => "Pad B",
+        "x" => "Pad X",
+        "y" => "Pad Y",
+        "back" => "Pad Back",
+        "guide" => "Pad Guide",
+        "start" => "Pad Start",
+        "leftstick" => "Pad Left Stick",
+        "rightstick" => "Pad Right Stick",
+        "leftshoulder" => "Pad LB",
+        "rightshoulder" => "Pad RB",
+        "dpup" => "Pad Up",
+        "dpdown" => "Pad Down",
+        "dpleft" => "Pad Left",
+        "dpright" => "Pad Right",
+        _ => return None,
+    })
+}
+
+/// The binding name for pushing either stick one way.
+pub fn stick_name(dir: Direction) -> Option<&'static str> {
+    match dir {
+        Direction::Up => Some("Pad Stick Up"),
+        Direction::Down => Some("Pad Stick Down"),
+        Direction::Left => Some("Pad Stick Left"),
+        Direction::Right => Some("Pad Stick Right"),
+        _ => None,
+    }
+}
+
+/// The ways an axis leans, toward its low end and its high end. The triggers don't lean either way.
+fn directions(axis: &str) -> Option<(Direction, Direction)> {
+    match axis {
+        "leftx" | "rightx" => Some((Direction::Left, Direction::Right)),
+        "lefty" | "righty" => Some((Direction::Up, Direction::Down)),
+        _ => None,
+    }
+}
+
+/// Which way the sticks of every pad are pushed, for turning stick movement into presses.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sticks {
+    /// -1, 0 or 1 for each axis, keyed by the pad's id and SDL's name for the axis
+    pushed: HashMap<(i32, String), i32>,
+}
+impl Sticks {
+    pub fn new() -> Sticks {
+        Sticks::default()
+    }
+    /// Takes the new `value` of an axis on pad `pad`. Returns the name of the press if the stick was
+    /// just pushed far enough one way. The triggers are axes too, but they don't make presses.
+    pub fn moved(&mut self, pad: i32, axis: &str, value: i16) -> Option<&'static str> {
+        let (back, forward) = directions(axis)?;
+        let value = value as i32;
+        let side = if value >= STICK_PUSH { 1 } else if value <= -STICK_PUSH { -1 } else { 0 };
+        let pushed = self.pushed.entry((pad, axis.to_string())).or_insert(0);
+        if value.abs() < STICK_RELEASE {
+            *pushed = 0;
+        }
+        if side == 0 || side == *pushed {
+            return None
+        }
+        *pushed = side;
+        stick_name(if side < 0 { back } else { forward })
+    }
+    /// The names of the ways the sticks are pushed right now, for things that go on while they're held.
+    pub fn held(&self) -> Vec<&'static str> {
+        self.pushed.iter()
+            .filter(|&(_, &side)| side != 0)
+            .filter_map(|((_, axis), &side)| {
+                let (back, forward) = directions(axis)?;
+                stick_name(if side < 0 { back } else { forward })
+            })
+            .collect()
+    }
+    /// Forgets a pad that's been unplugged.
+    pub fn forget(&mut self, pad: i32) {
+        self.pushed.retain(|k, _| k.0 != pad);
+    }
+}
@@ -0,0 +1,147 @@
+//! Which squares the active pusher can walk to without pushing anything, and how to get there.
+
+use std::collections::VecDeque;
+
+use {Direction, GameState, Level, Position, Tile};
+
+const UNREACHED: usize = usize::MAX;
+
+/// The squares the active pusher can walk to in one position, worked out with the same rules
+/// as `Game::make_move`: sliding over ice, taking teleporters and keeping to one-way floors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reachability {
+    width: usize,
+    /// What the position looked like when this was worked out
+    players: Vec<Position>,
+    active: usize,
+    stars: Vec<Position>,
+    filled_holes: Vec<Position>,
+    /// For every square, how many steps it takes to walk there, or UNREACHED
+    steps: Vec<usize>,
+    /// For every square that was reached, the square and direction of the last step there
+    parents: Vec<Option<(usize, Direction)>>,
+}
+impl Reachability {
+    pub fn new(level: &Level, state: &GameState) -> Reachability {
+        let (width, height) = (level.width, level.height);
+        let mut blocked = level.map.iter()
+                                   .flat_map(|row| row.iter().map(|&t| t == Tile::Wall))
+                                   .collect::<Vec<_>>();
+        let on_map = |pos: Position| pos.x < width && pos.y < height;
+        for star in state.stars.iter().filter(|s| on_map(s.position)) {
+            blocked[star.position.y * width + star.position.x] = true;
+        }
+        for (i, player) in state.players.iter().enumerate() {
+            if i != state.active && on_map(player.position) {
+                blocked[player.position.y * width + player.position.x] = true;
+            }
+        }
+        let is_blocked = |x: i32, y: i32| x < 0 || y < 0 || x >= width as i32 || y >= height as i32
+                                          || blocked[y as usize * width + x as usize];
+        let can_enter = |x: i32, y: i32, dir: Direction| !is_blocked(x, y) && match level.map[y as usize][x as usize] {
+            Tile::OneWay(way) => way == dir,
+            Tile::Hole => state.filled_holes.contains(&Position::new(x as usize, y as usize)),
+            _ => true,
+        };
+
+        let mut steps = vec![UNREACHED; width * height];
+        let mut parents = vec![None; width * height];
+        let start = state.player().position;
+        let mut queue = VecDeque::new();
+        if on_map(start) {
+            steps[start.y * width + start.x] = 0;
+            queue.push_back(start);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let here = pos.y * width + pos.x;
+            for &dir in level.topology.directions() {
+                let (x_off, y_off) = level.topology.offset(dir).unwrap();
+                let (mut x, mut y) = (pos.x as i32 + x_off, pos.y as i32 + y_off);
+                if !can_enter(x, y, dir) {
+                    continue
+                }
+                // Follow the square along like Game::settle_player does
+                loop {
+                    match level.map[y as usize][x as usize] {
+                        Tile::Teleporter(_) => {
+                            let exit = level.teleporter_exit(Position::new(x as usize, y as usize)).unwrap();
+                            if !is_blocked(exit.x as i32, exit.y as i32) {
+                                x = exit.x as i32;
+                                y = exit.y as i32;
+                            }
+                        },
+                        Tile::Ice if can_enter(x + x_off, y + y_off, dir) => {
+                            x += x_off;
+                            y += y_off;
+                            continue
+                        },
+                        _ => (),
+                    }
+                    break
+                }
+                let there = y as usize * width + x as usize;
+                if steps[there] == UNREACHED {
+                    steps[there] = steps[here] + 1;
+                    parents[there] = Some((here, dir));
+                    queue.push_back(Position::new(x as usize, y as usize));
+                }
+            }
+        }
+        Reachability {
+            width,
+            players: state.players.iter().map(|p| p.position).collect(),
+            active: state.active,
+            stars: state.stars.iter().map(|s| s.position).collect(),
+            filled_holes: state.filled_holes.clone(),
+            steps,
+            parents,
+        }
+    }
+    /// Whether this was worked out for `state`, so it can be used again.
+    pub fn is_for(&self, state: &GameState) -> bool {
+        self.active == state.active
+            && self.players.len() == state.players.len()
+            && self.players.iter().zip(&state.players).all(|(&p, q)| p == q.position)
+            && self.stars.len() == state.stars.len()
+            && self.stars.iter().zip(&state.stars).all(|(&p, s)| p == s.position)
+            && self.filled_holes == state.filled_holes
+    }
+    fn index(&self, pos: Position) -> Option<usize> {
+        let i = pos.y * self.width + pos.x;
+        if pos.x < self.width && i < self.steps.len() { Some(i) } else { None }
+    }
+    pub fn is_reachable(&self, pos: Position) -> bool {
+        self.distance(pos).is_some()
+    }
+    /// The number of steps it takes to walk to `pos`.
+    pub fn distance(&self, pos: Position) -> Option<usize> {
+        self.index(pos).map(|i| self.steps[i]).filter(|&s| s != UNREACHED)
+    }
+    /// The shortest walk to `pos`, if there is one.
+    pub fn path_to(&self, pos: Position) -> Option<Vec<Direction>> {
+        let mut i = self.index(pos)?;
+        if self.steps[i] == UNREACHED {
+            return None
+        }
+        let mut path = Vec::with_capacity(self.steps[i]);
+        while let Some((parent, dir)) = self.parents[i] {
+            path.push(dir);
+            i = parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+    /// Every square the pusher can walk to, in reading order.
+    pub fn positions(&self) -> Vec<Position> {
+        self.steps.iter()
+                  .enumerate()
+                  .filter(|s| *s.1 != UNREACHED)
+                  .map(|(i, _)| Position::new(i % self.width, i / self.width))
+                  .collect()
+    }
+    /// The first square the pusher can walk to in reading order. On levels where every step can
+    /// be walked back, pusher positions that can walk to each other all get the same one.
+    pub fn normalized(&self) -> Position {
+        self.positions().first().cloned().unwrap_or_else(|| self.players[self.active])
+    }
+}
@@ -0,0 +1,88 @@
+//! The best solution found for every level, keyed by the level's canonical hash so the
+//! record follows a puzzle into whichever pack it turns up in.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+/// Stands in for the solution of a level that starts solved, so its line still has every field.
+const EMPTY_SOLUTION: &str = "-";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Record {
+    pub moves: usize,
+    pub pushes: usize,
+    pub solution: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveData {
+    path: PathBuf,
+    records: HashMap<u64, Record>,
+}
+impl SaveData {
+    /// Loads the save file at `path`. A missing or broken file just means nothing has been solved yet.
+    pub fn load(path: PathBuf) -> SaveData {
+        let mut records = HashMap::new();
+        if let Ok(f) = File::open(&path) {
+            for line in BufReader::new(f).lines().map_while(Result::ok) {
+                // Every line is "<hash> <moves> <pushes> <solution>". Older files left out an empty solution.
+                let fields = line.split_whitespace().collect::<Vec<_>>();
+                if fields.len() != 3 && fields.len() != 4 {
+                    continue
+                }
+                if let (Ok(hash), Ok(moves), Ok(pushes)) = (u64::from_str_radix(fields[0], 16), fields[1].parse(), fields[2].parse()) {
+                    let solution = match fields.get(3) {
+                        Some(&solution) if solution != EMPTY_SOLUTION => solution.to_string(),
+                        _ => String::new(),
+                    };
+                    records.insert(hash, Record { moves, pushes, solution });
+                }
+            }
+        }
+        SaveData { path, records }
+    }
+    pub fn best(&self, hash: u64) -> Option<&Record> {
+        self.records.get(&hash)
+    }
+    /// Keeps `record` if it beats the best one for the level so far, by moves and then pushes.
+    /// Returns whether it did.
+    pub fn record(&mut self, hash: u64, record: Record) -> bool {
+        let better = self.best(hash).is_none_or(|best| (record.moves, record.pushes) < (best.moves, best.pushes));
+        if better {
+            self.records.insert(hash, record);
+        }
+        better
+    }
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut hashes = self.records.keys().collect::<Vec<_>>();
+        hashes.sort();
+        let mut f = File::create(&self.path)?;
+        for hash in hashes {
+            let r = &self.records[hash];
+            let solution = if r.solution.is_empty() { EMPTY_SOLUTION } else { &r.solution };
+            writeln!(f, "{:016x} {} {} {}", hash, r.moves, r.pushes, solution)?;
+        }
+        Ok(())
+    }
+}
+
+/// Where the game keeps its files: ~/.sokoban-rs, or %APPDATA%\sokoban-rs on Windows.
+pub fn data_dir() -> PathBuf {
+    if let Some(appdata) = env::var_os("APPDATA") {
+        PathBuf::from(appdata).join("sokoban-rs")
+    } else if let Some(home) = env::var_os("HOME") {
+        PathBuf::from(home).join(".sokoban-rs")
+    } else {
+        PathBuf::from(".sokoban-rs")
+    }
+}
+
+pub fn default_path() -> PathBuf {
+    data_dir().join("solutions.txt")
+}
@@ -0,0 +1,154 @@
+//! Rotating and mirroring levels.
+
+use {Direction, GameState, Goal, Level, Player, Position, Star, Tile, Topology};
+
+/// One of the eight ways to turn a square grid into itself.
+/// The grid is mirrored left to right first, and then turned clockwise.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transform {
+    pub quarter_turns: u8,
+    pub mirrored: bool,
+}
+impl Transform {
+    pub fn new(quarter_turns: u8, mirrored: bool) -> Transform {
+        Transform { quarter_turns: quarter_turns % 4, mirrored }
+    }
+    pub fn all() -> Vec<Transform> {
+        (0..8).map(|i| Transform::new(i % 4, i >= 4)).collect()
+    }
+    /// This transform followed by `next`.
+    pub fn then(&self, next: Transform) -> Transform {
+        if next.mirrored {
+            // Mirroring after turning is the same as turning the other way after mirroring
+            Transform::new(next.quarter_turns + 4 - self.quarter_turns, !self.mirrored)
+        } else {
+            Transform::new(self.quarter_turns + next.quarter_turns, self.mirrored)
+        }
+    }
+    /// The transform that undoes this one.
+    pub fn inverse(&self) -> Transform {
+        if self.mirrored {
+            *self
+        } else {
+            Transform::new(4 - self.quarter_turns, false)
+        }
+    }
+    pub fn is_identity(&self) -> bool {
+        self.quarter_turns == 0 && !self.mirrored
+    }
+    pub fn name(&self) -> String {
+        let turned = match self.quarter_turns {
+            0 => "",
+            1 => "Turned 90",
+            2 => "Turned 180",
+            _ => "Turned 270",
+        };
+        match (turned, self.mirrored) {
+            ("", false) => "Original".to_string(),
+            ("", true) => "Mirrored".to_string(),
+            (turned, false) => turned.to_string(),
+            (turned, true) => format!("Mirrored, {}", turned.to_lowercase()),
+        }
+    }
+    /// The width and height of a `width` by `height` grid after the transform.
+    pub fn dimensions(&self, width: usize, height: usize) -> (usize, usize) {
+        if self.quarter_turns.is_multiple_of(2) {
+            (width, height)
+        } else {
+            (height, width)
+        }
+    }
+    pub fn position(&self, pos: Position, width: usize, height: usize) -> Position {
+        let (mut x, mut y) = (pos.x, pos.y);
+        let (mut w, mut h) = (width, height);
+        if self.mirrored {
+            x = w - 1 - x;
+        }
+        for _ in 0..self.quarter_turns {
+            let turned = (h - 1 - y, x);
+            x = turned.0;
+            y = turned.1;
+            let dims = (h, w);
+            w = dims.0;
+            h = dims.1;
+        }
+        Position::new(x, y)
+    }
+    pub fn direction(&self, dir: Direction) -> Direction {
+        let mut dir = if self.mirrored {
+            match dir {
+                Direction::Left => Direction::Right,
+                Direction::Right => Direction::Left,
+                Direction::UpLeft => Direction::UpRight,
+                Direction::UpRight => Direction::UpLeft,
+                Direction::DownLeft => Direction::DownRight,
+                Direction::DownRight => Direction::DownLeft,
+                d => d,
+            }
+        } else {
+            dir
+        };
+        for _ in 0..self.quarter_turns {
+            dir = match dir {
+                Direction::Up => Direction::Right,
+                Direction::Right => Direction::Down,
+                Direction::Down => Direction::Left,
+                Direction::Left => Direction::Up,
+                Direction::UpLeft => Direction::UpRight,
+                Direction::UpRight => Direction::DownRight,
+                Direction::DownRight => Direction::DownLeft,
+                Direction::DownLeft => Direction::UpLeft,
+            };
+        }
+        dir
+    }
+    /// Transforms the moves of a LURD solution, keeping whether they're pushes.
+    /// Anything that isn't a move, like run lengths and whitespace, is kept as it is.
+    pub fn lurd(&self, solution: &str) -> String {
+        solution.chars().map(|c| match Direction::from_lurd(c) {
+            Some(dir) => self.direction(dir).lurd(c.is_uppercase()),
+            None => c,
+        }).collect()
+    }
+    /// Transforms a state of a `width` by `height` level.
+    pub fn state(&self, state: &GameState, width: usize, height: usize) -> GameState {
+        let pos = |p| self.position(p, width, height);
+        GameState {
+            players: state.players.iter().map(|p| Player::new(pos(p.position), self.direction(p.direction))).collect(),
+            stars: state.stars.iter().map(|s| Star { position: pos(s.position), ..*s }).collect(),
+            goals: state.goals.iter().map(|g| Goal { position: pos(g.position), ..*g }).collect(),
+            filled_holes: state.filled_holes.iter().map(|&h| pos(h)).collect(),
+            ..state.clone()
+        }
+    }
+    pub fn level(&self, level: &Level) -> Result<Level, String> {
+        if level.topology != Topology::Square {
+            return Err("Hexoban levels can't be turned or mirrored".to_string());
+        }
+        let (width, height) = self.dimensions(level.width, level.height);
+        let mut map = vec![vec![Tile::OutsideFloor; width]; height];
+        for (y, row) in level.map.iter().enumerate() {
+            for (x, &tile) in row.iter().enumerate() {
+                let pos = self.position(Position::new(x, y), level.width, level.height);
+                map[pos.y][pos.x] = match tile {
+                    Tile::OneWay(dir) => Tile::OneWay(self.direction(dir)),
+                    tile => tile,
+                };
+            }
+        }
+        Ok(Level {
+            width,
+            height,
+            topology: level.topology,
+            map,
+            start_state: self.state(&level.start_state, level.width, level.height),
+            metadata: level.metadata.iter().map(|m| {
+                if m.0.eq_ignore_ascii_case("Solution") {
+                    (m.0.clone(), self.lurd(&m.1))
+                } else {
+                    m.clone()
+                }
+            }).collect(),
+        })
+    }
+}
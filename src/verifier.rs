@@ -0,0 +1,74 @@
+//! Checks solutions by replaying them with the same rules the game uses.
+
+use {Direction, Game, Level, metadata_value};
+
+/// What happened when a solution was replayed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Verification {
+    pub solved: bool,
+    /// Moves and pushes made before the solution ended or hit an illegal move
+    pub moves: usize,
+    pub pushes: usize,
+    /// The index of the character in the solution that couldn't be played, if there was one
+    pub first_illegal: Option<usize>,
+}
+
+/// Splits a LURD solution into its moves, each with the index of its character in `solution`
+/// and whether it's written as a push. Whitespace is skipped, and a number in front of a move
+/// repeats it, as in "3r". Gives the index of the first character that isn't part of a move if there is one.
+pub fn parse(solution: &str) -> Result<Vec<(usize, Direction, bool)>, usize> {
+    let mut moves = Vec::new();
+    let mut count: Option<usize> = None;
+    for (i, c) in solution.chars().enumerate() {
+        if c.is_whitespace() {
+            continue
+        } else if let Some(digit) = c.to_digit(10) {
+            count = Some(count.unwrap_or(0) * 10 + digit as usize);
+        } else if let Some(dir) = Direction::from_lurd(c) {
+            for _ in 0..count.take().unwrap_or(1) {
+                moves.push((i, dir, c.is_uppercase()));
+            }
+        } else {
+            return Err(i)
+        }
+    }
+    Ok(moves)
+}
+
+/// Makes a move of a solution and reports whether it's legal as written:
+/// a move written as a push has to push, and one written as a walk mustn't.
+pub fn step(game: &mut Game, dir: Direction, push: bool) -> bool {
+    let (steps, pushes) = (game.state.steps, game.state.pushes);
+    game.make_move(dir);
+    game.state.steps != steps && (game.state.pushes > pushes) == push
+}
+
+/// Replays `solution` on `level` and reports whether it solves it.
+pub fn verify(level: &Level, solution: &str) -> Verification {
+    let mut game = Game::from_level(level.clone());
+    let mut first_illegal = None;
+    match parse(solution) {
+        Ok(moves) => {
+            for (i, dir, push) in moves {
+                if !step(&mut game, dir, push) {
+                    first_illegal = Some(i);
+                    break
+                }
+            }
+        },
+        Err(i) => first_illegal = Some(i),
+    }
+    Verification {
+        solved: first_illegal.is_none() && game.solved(),
+        moves: game.state.steps,
+        pushes: game.state.pushes,
+        first_illegal,
+    }
+}
+
+/// Verifies the "Solution" of every level that has one. Levels without a solution give None.
+pub fn verify_levels(levels: &[Level]) -> Vec<Option<Verification>> {
+    levels.iter()
+          .map(|level| metadata_value(&level.metadata, "Solution").map(|solution| verify(level, solution)))
+          .collect()
+}
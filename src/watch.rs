@@ -0,0 +1,45 @@
+//! Noticing when a file is changed on disk, by looking at its size and modification time now and then.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// How often `FileWatch::poll` looks at the file.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileWatch {
+    pub path: PathBuf,
+    seen: Option<(SystemTime, u64)>,
+    checked: Instant,
+}
+impl FileWatch {
+    pub fn new(path: PathBuf) -> FileWatch {
+        let seen = stamp(&path);
+        FileWatch { path, seen, checked: Instant::now() }
+    }
+    /// Whether the file has changed since it was last seen, looking at most once every `POLL_INTERVAL`.
+    pub fn poll(&mut self) -> bool {
+        if self.checked.elapsed() < POLL_INTERVAL {
+            return false
+        }
+        self.check()
+    }
+    /// Whether the file has changed since it was last seen.
+    /// A file that's gone missing hasn't changed yet, since editors often replace files by deleting and renaming.
+    pub fn check(&mut self) -> bool {
+        self.checked = Instant::now();
+        match stamp(&self.path) {
+            Some(stamp) if Some(stamp) != self.seen => {
+                self.seen = Some(stamp);
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+fn stamp(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
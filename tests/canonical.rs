@@ -0,0 +1,54 @@
+//! Recognising a level however it's turned, numbered or laid out.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::canonical;
+use sokoban_rs::transform::Transform;
+use sokoban_rs::verifier;
+
+use common::level;
+
+const LEVEL: &str = "#######\n#.   .#\n#  #  #\n# $@$ #\n#     #\n#######";
+
+#[test]
+fn turned_copies_hash_the_same() {
+    let level = level(LEVEL);
+    let hash = canonical::hash(&level);
+    for t in Transform::all() {
+        assert_eq!(canonical::hash(&t.level(&level).unwrap()), hash, "{}", t.name());
+    }
+    // Walls nobody can reach and extra space around the level don't count
+    let padded = format!("   \n{}", LEVEL.lines().map(|l| format!("##{}", l)).collect::<Vec<_>>().join("\n"));
+    assert_eq!(canonical::hash(&self::level(&padded)), hash);
+    assert_ne!(canonical::hash(&self::level("#####\n#@$.#\n#####")), hash);
+}
+
+#[test]
+fn color_numbers_dont_matter() {
+    let colored = |stars: &str, goals: &str| {
+        canonical::hash(&level(&format!("Star colors: {}\nGoal colors: {}\n{}", stars, goals, LEVEL)))
+    };
+    assert_eq!(colored("1 2", "2 1"), colored("5 3", "3 5"));
+    assert_ne!(colored("1 2", "2 1"), colored("1 2", "1 2"));
+    assert_ne!(colored("1 2", "2 1"), canonical::hash(&level(LEVEL)));
+}
+
+#[test]
+fn hashes_dont_change_between_versions() {
+    // Save files are keyed by this, so changing it loses everyone's records
+    assert_eq!(canonical::hash(&level("#####\n#@$.#\n#####")), 0xbb4b_a472_4836_0bf3);
+}
+
+#[test]
+fn saved_solutions_play_on_turned_copies() {
+    let level = level("######\n#@   #\n# $  #\n#   .#\n######");
+    assert!(verifier::verify(&level, "dRRurD").solved);
+    let kept = canonical::to_canonical(&level, "dRRurD");
+    for t in Transform::all() {
+        let turned = t.level(&level).unwrap();
+        assert_eq!(canonical::to_canonical(&turned, &t.lurd("dRRurD")), kept, "{}", t.name());
+        assert!(verifier::verify(&turned, &canonical::from_canonical(&turned, &kept)).solved, "{}", t.name());
+    }
+}
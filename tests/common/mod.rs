@@ -0,0 +1,49 @@
+//! Levels shared by the tests.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use sokoban_rs::{Game, Level, load_levels};
+
+/// Every level file that comes with the game, by name.
+pub const LEVEL_FILES: [(&str, &str); 5] = [
+    ("levels.txt", include_str!("../../levels.txt")),
+    ("plus.txt", include_str!("../../plus.txt")),
+    ("colored.txt", include_str!("../../colored.txt")),
+    ("multiban.txt", include_str!("../../multiban.txt")),
+    ("hexoban.txt", include_str!("../../hexoban.txt")),
+];
+
+/// Every bundled level, in file order.
+#[allow(dead_code)]
+pub fn all_levels() -> Vec<Level> {
+    LEVEL_FILES.iter()
+               .flat_map(|&(name, text)| load_levels(text).unwrap_or_else(|e| panic!("{}: {}", name, e)))
+               .collect()
+}
+
+/// Parses a single level from its lines.
+#[allow(dead_code)]
+pub fn level(text: &str) -> Level {
+    let mut levels = load_levels(text).unwrap();
+    assert_eq!(levels.len(), 1, "expected exactly one level");
+    levels.remove(0)
+}
+
+/// A corridor 40 tiles long, with the pusher at the left end, for a level far wider than the screen.
+#[allow(dead_code)]
+pub fn corridor() -> Game {
+    let wall = "#".repeat(40);
+    Game::from_level(level(&format!("{}\n#@{}$.#\n{}", wall, " ".repeat(35), wall)))
+}
+
+/// A fresh, empty directory for a test to make files in.
+#[allow(dead_code)]
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("sokoban-rs-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
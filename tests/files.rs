@@ -0,0 +1,75 @@
+extern crate sokoban_rs;
+
+mod common;
+
+use std::fs::{self, File};
+
+use sokoban_rs::files::{self, EntryKind, RecentFiles, MAX_RECENT};
+
+use common::scratch_dir;
+
+#[test]
+fn directories_come_before_level_files() {
+    let dir = scratch_dir("list");
+    for file in &["b.txt", "a.SOK", "c.xsb", "notes.md", ".hidden.txt"] {
+        File::create(dir.join(file)).unwrap();
+    }
+    fs::create_dir(dir.join("packs")).unwrap();
+    fs::create_dir(dir.join(".git")).unwrap();
+    let entries = files::list_dir(&dir).unwrap();
+    let names = entries.iter().map(|e| e.name()).collect::<Vec<_>>();
+    assert_eq!(names, ["../", "packs/", "a.SOK", "b.txt", "c.xsb"]);
+    assert_eq!(entries[0].kind, EntryKind::Parent);
+    assert_eq!(entries[1].kind, EntryKind::Directory);
+    assert_eq!(entries[2].kind, EntryKind::LevelFile);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn missing_directories_are_an_error() {
+    let dir = scratch_dir("missing").join("nothing here");
+    assert!(files::list_dir(&dir).is_err());
+}
+
+#[test]
+fn search_ignores_case_and_drops_the_way_up() {
+    let dir = scratch_dir("filter");
+    for file in &["Microban.txt", "original.txt", "sasquatch.sok"] {
+        File::create(dir.join(file)).unwrap();
+    }
+    let entries = files::list_dir(&dir).unwrap();
+    assert_eq!(files::filter(&entries, "").len(), 4);
+    let names = |query| files::filter(&entries, query).iter().map(|e| e.name()).collect::<Vec<_>>();
+    assert_eq!(names("MICRO"), ["Microban.txt"]);
+    assert_eq!(names(".txt"), ["Microban.txt", "original.txt"]);
+    assert!(names("xyz").is_empty());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn recent_files_keep_the_newest_first() {
+    let dir = scratch_dir("recent");
+    let level_files = (0..MAX_RECENT + 2).map(|i| dir.join(format!("{}.txt", i))).collect::<Vec<_>>();
+    for path in &level_files {
+        File::create(path).unwrap();
+    }
+    let list = dir.join("data").join("recent.txt");
+    let mut recent = RecentFiles::load(list.clone());
+    assert!(recent.files.is_empty());
+    for path in &level_files {
+        recent.add(path);
+    }
+    // Opening a file again moves it back to the top
+    recent.add(&level_files[5]);
+    recent.save().unwrap();
+
+    let recent = RecentFiles::load(list);
+    assert_eq!(recent.files.len(), MAX_RECENT);
+    let names = recent.files.iter().map(|f| f.file_name().unwrap().to_string_lossy().into_owned()).collect::<Vec<_>>();
+    assert_eq!(&names[..3], ["5.txt", "11.txt", "10.txt"]);
+
+    // Files that have gone away aren't offered
+    fs::remove_file(&level_files[11]).unwrap();
+    assert_eq!(recent.entries().len(), MAX_RECENT - 1);
+    fs::remove_dir_all(&dir).unwrap();
+}
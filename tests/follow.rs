@@ -0,0 +1,85 @@
+//! The camera following the pusher around a level too big for the screen.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use std::time::Duration;
+
+use sokoban_rs::{Direction, Game, HALF_WIDTH, TILE_HEIGHT, TILE_WIDTH};
+use sokoban_rs::follow::{self, Follow, DEAD_ZONE};
+
+use common::corridor;
+
+/// Runs the camera for a second at 60 frames a second.
+fn settle(follow: &mut Follow, game: &mut Game) {
+    for _ in 0..60 {
+        follow.update(game, Duration::from_millis(16));
+    }
+}
+
+fn camera(game: &Game) -> (i32, i32) {
+    (game.camera.x_offset, game.camera.y_offset)
+}
+
+#[test]
+fn the_camera_pans_as_far_as_the_level_reaches() {
+    let game = corridor();
+    // Half the level's 2560 pixels, less half the screen, and a tile to spare
+    assert_eq!(game.camera.max_x_pan, 1280 - 450 + TILE_WIDTH as i32);
+    // The level fits on the screen top to bottom
+    assert_eq!(game.camera.max_y_pan, TILE_HEIGHT as i32);
+    // Centering on the pusher at the far left goes as far as the camera pans
+    assert_eq!(follow::centered(&game), (game.camera.max_x_pan, 0));
+}
+
+#[test]
+fn the_camera_stays_put_unless_following() {
+    let mut game = corridor();
+    let mut follow = Follow::new(false);
+    settle(&mut follow, &mut game);
+    for _ in 0..20 {
+        game.make_move(Direction::Right);
+        follow.update(&mut game, Duration::from_millis(16));
+    }
+    settle(&mut follow, &mut game);
+    assert_eq!(camera(&game), (0, 0));
+}
+
+#[test]
+fn following_keeps_the_pusher_in_the_dead_zone() {
+    let mut game = corridor();
+    let mut follow = Follow::new(true);
+    settle(&mut follow, &mut game);
+    // Walking at a steady eight steps a second
+    for _ in 0..35 {
+        game.make_move(Direction::Right);
+        for _ in 0..8 {
+            follow.update(&mut game, Duration::from_millis(16));
+            let off_center = camera(&game).0 - follow::centered(&game).0;
+            // It lags behind a little while it catches up, but the pusher never leaves the screen
+            assert!(off_center.abs() < (HALF_WIDTH - TILE_WIDTH / 2) as i32, "{}", off_center);
+        }
+    }
+    settle(&mut follow, &mut game);
+    assert_eq!(camera(&game).0 - follow::centered(&game).0, DEAD_ZONE.0);
+}
+
+#[test]
+fn centering_scrolls_back_to_the_pusher() {
+    let mut game = corridor();
+    let mut follow = Follow::new(true);
+    for _ in 0..10 {
+        game.make_move(Direction::Right);
+    }
+    settle(&mut follow, &mut game);
+    // Panning away by hand sticks until the pusher moves again
+    game.camera.pan(-500, 0);
+    let panned = camera(&game);
+    settle(&mut follow, &mut game);
+    assert_eq!(camera(&game), panned);
+
+    follow.center();
+    settle(&mut follow, &mut game);
+    assert_eq!(camera(&game), follow::centered(&game));
+}
@@ -0,0 +1,32 @@
+//! Made up levels.
+
+extern crate sokoban_rs;
+
+use sokoban_rs::generator::{self, Settings};
+use sokoban_rs::{cli, metadata_value, verifier};
+
+const SMALL: Settings = Settings { width: 2, height: 2, stars: 1, difficulty: 3, attempts: 2 };
+
+#[test]
+fn generated_levels_come_with_their_solution() {
+    let level = generator::generate(SMALL, 7).unwrap();
+    let solution = metadata_value(&level.metadata, "Solution").unwrap();
+    assert!(verifier::verify(&level, solution).solved);
+    // The same seed gives the same level
+    assert_eq!(generator::generate(SMALL, 7).unwrap().map, level.map);
+}
+
+#[test]
+fn levels_can_be_made_in_the_background() {
+    let level = generator::spawn(SMALL, 7).recv().unwrap().unwrap();
+    assert_eq!(level.map, generator::generate(SMALL, 7).unwrap().map);
+    let none = Settings { stars: 0, ..SMALL };
+    assert!(generator::spawn(none, 7).recv().unwrap().is_err());
+}
+
+#[test]
+fn seeds_wrap_around_at_the_top() {
+    let options = ["--seed", "18446744073709551615", "--count", "2", "--size", "2x2", "--stars", "1", "--difficulty", "3", "--attempts", "2"];
+    let options = options.iter().map(|o| o.to_string()).collect::<Vec<_>>();
+    assert!(cli::generate_levels(&options).is_ok());
+}
@@ -0,0 +1,151 @@
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::{Direction, Game, Position, load_levels};
+use sokoban_rs::verifier;
+
+use common::{LEVEL_FILES, all_levels, level};
+
+/// How many levels each bundled file holds.
+const LEVEL_COUNTS: [(&str, usize); 5] = [
+    ("levels.txt", 201),
+    ("plus.txt", 2),
+    ("colored.txt", 1),
+    ("multiban.txt", 1),
+    ("hexoban.txt", 1),
+];
+
+/// Levels with a known shortest solution and the moves and pushes it takes.
+const GOLDEN: [(&str, &str, usize, usize); 5] = [
+    ("#####\n#@$.#\n#####", "R", 1, 1),
+    ("#####\n#+$ #\n#   #\n#####", "drruL", 5, 1),
+    ("#####\n#@*.#\n# $ #\n#   #\n#####", "RlddrU", 6, 2),
+    ("Topology: Hexoban\n # # # # #\n# @ $ . #\n # # # # #", "R", 1, 1),
+    ("##########\n#@ $~~~ .#\n##########", "rRrR", 4, 2),
+];
+
+#[test]
+fn bundled_files_hold_every_level() {
+    for (&(name, text), &(counted, count)) in LEVEL_FILES.iter().zip(&LEVEL_COUNTS) {
+        assert_eq!(name, counted);
+        assert_eq!(load_levels(text).unwrap().len(), count, "{}", name);
+    }
+}
+
+#[test]
+fn golden_solutions_solve_their_levels() {
+    for &(text, solution, moves, pushes) in &GOLDEN {
+        let result = verifier::verify(&level(text), solution);
+        assert!(result.solved, "{} doesn't solve\n{}", solution, text);
+        assert_eq!((result.moves, result.pushes), (moves, pushes), "{}", text);
+    }
+}
+
+#[test]
+fn golden_levels_arent_solved_by_less() {
+    for &(text, solution, _, _) in &GOLDEN {
+        let level = level(text);
+        let mut game = Game::from_level(level.clone());
+        assert!(!game.solved());
+        let moves = verifier::parse(solution).unwrap();
+        for &(_, dir, _) in &moves[..moves.len() - 1] {
+            game.make_move(dir);
+            assert!(!game.solved(), "{} is solved early\n{}", solution, text);
+        }
+    }
+}
+
+#[test]
+fn demo_level_is_solved_by_hand() {
+    let level = load_levels(LEVEL_FILES[0].1).unwrap().remove(0);
+    assert_eq!((level.width, level.height), (9, 9));
+    assert_eq!(level.start_state.stars.len(), 4);
+    let result = verifier::verify(&level, "UdLrRlD");
+    assert!(result.solved);
+    assert_eq!((result.moves, result.pushes), (7, 4));
+}
+
+#[test]
+fn pushes_have_to_be_written_as_pushes() {
+    let level = level("######\n#@ $.#\n######");
+    assert!(verifier::verify(&level, "rR").solved);
+    // The push is written as a walk
+    let result = verifier::verify(&level, "r r");
+    assert!(!result.solved);
+    assert_eq!(result.first_illegal, Some(2));
+    // And the walk as a push
+    assert_eq!(verifier::verify(&level, "RR").first_illegal, Some(0));
+}
+
+#[test]
+fn bundled_solutions_verify() {
+    let levels = all_levels();
+    for (i, result) in verifier::verify_levels(&levels).into_iter().enumerate() {
+        if let Some(result) = result {
+            assert!(result.solved, "level {}: {:?}", i + 1, result);
+        }
+    }
+}
+
+#[test]
+fn pushers_stop_at_the_far_wall() {
+    let mut game = Game::from_level(level("##########\n#@      .#\n##########"));
+    for _ in 0..20 {
+        game.make_move(Direction::Right);
+    }
+    assert_eq!(game.state.player().position, Position::new(8, 1));
+    assert_eq!(game.state.steps, 7);
+}
+
+#[test]
+fn stars_stop_at_the_far_wall() {
+    let mut game = Game::from_level(level("##########\n#@$    . #\n##########"));
+    for _ in 0..20 {
+        game.make_move(Direction::Right);
+    }
+    assert_eq!(game.state.stars[0].position, Position::new(8, 1));
+    assert_eq!(game.state.player().position, Position::new(7, 1));
+    assert!(!game.solved());
+}
+
+#[test]
+fn pushers_cant_leave_an_open_level() {
+    let mut game = Game::from_level(level("@$ ."));
+    game.make_move(Direction::Left);
+    game.make_move(Direction::Up);
+    game.make_move(Direction::Down);
+    assert_eq!(game.state.player().position, Position::new(0, 0));
+    assert_eq!(game.state.steps, 0);
+    for _ in 0..5 {
+        game.make_move(Direction::Right);
+    }
+    assert_eq!(game.state.stars[0].position, Position::new(3, 0));
+    assert!(game.solved());
+}
+
+#[test]
+fn pushers_block_each_other() {
+    let mut game = Game::from_level(level("#######\n#.@@$.#\n#######"));
+    game.make_move(Direction::Right);
+    assert_eq!(game.state.steps, 0);
+    game.state.switch_player();
+    game.make_move(Direction::Right);
+    assert_eq!(game.state.player().position, Position::new(4, 1));
+    assert_eq!(game.state.stars[0].position, Position::new(5, 1));
+}
+
+#[test]
+fn stars_need_a_goal_of_their_color() {
+    let colored = "Star colors: 1\nGoal colors: 2 1\n#######\n#.@$ .#\n#######";
+    let mut game = Game::from_level(level(colored));
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Right);
+    assert_eq!(game.state.stars[0].position, Position::new(5, 1));
+    assert!(game.solved());
+
+    let mut game = Game::from_level(level("Star colors: 1\nGoal colors: 1 2\n#######\n#.@$ .#\n#######"));
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Right);
+    assert!(!game.solved());
+}
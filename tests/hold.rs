@@ -0,0 +1,91 @@
+//! Held keys, timed by made-up frame lengths rather than a real clock.
+
+extern crate sokoban_rs;
+
+use std::time::Duration;
+
+use sokoban_rs::Camera;
+use sokoban_rs::hold::{Pan, Repeat, PAN_SPEED};
+use sokoban_rs::keys::Action;
+
+fn ms(n: u64) -> Duration {
+    Duration::from_millis(n)
+}
+
+/// How many moves a key held for `frames` frames of `frame` each makes after its press.
+fn repeats(frame: Duration, frames: u32) -> u32 {
+    let mut repeat = Repeat::new(ms(250), 10);
+    repeat.press(Action::MoveLeft);
+    (0..frames).filter_map(|_| repeat.update(&[Action::MoveLeft], frame)).map(|r| r.1).sum()
+}
+
+#[test]
+fn moves_repeat_at_the_same_rate_at_any_frame_rate() {
+    // Held for a second: once at 250ms, then every 100ms after
+    assert_eq!(repeats(ms(10), 100), 8);
+    assert_eq!(repeats(ms(50), 20), 8);
+    assert_eq!(repeats(ms(125), 8), 8);
+    // Not held long enough to repeat
+    assert_eq!(repeats(ms(20), 12), 0);
+}
+
+#[test]
+fn letting_go_stops_the_repeat() {
+    let mut repeat = Repeat::new(ms(100), 10);
+    repeat.press(Action::MoveUp);
+    assert_eq!(repeat.update(&[Action::MoveUp], ms(150)), Some((Action::MoveUp, 1)));
+    assert_eq!(repeat.update(&[], ms(150)), None);
+    assert_eq!(repeat.update(&[], ms(500)), None);
+    // Keys that aren't moves don't repeat
+    repeat.press(Action::Undo);
+    assert_eq!(repeat.update(&[Action::Undo], ms(500)), None);
+}
+
+#[test]
+fn a_move_key_still_held_takes_over() {
+    let mut repeat = Repeat::new(ms(100), 10);
+    repeat.press(Action::MoveUp);
+    assert_eq!(repeat.update(&[Action::MoveUp, Action::MoveLeft], ms(50)), None);
+    // Up is let go while Left is still down, so Left repeats after the delay
+    assert_eq!(repeat.update(&[Action::MoveLeft], ms(50)), None);
+    assert_eq!(repeat.update(&[Action::MoveLeft], ms(50)), None);
+    assert_eq!(repeat.update(&[Action::MoveLeft], ms(50)), Some((Action::MoveLeft, 1)));
+}
+
+#[test]
+fn a_stalled_frame_doesnt_fire_a_burst() {
+    let mut repeat = Repeat::new(ms(0), 100);
+    repeat.press(Action::MoveRight);
+    let (_, times) = repeat.update(&[Action::MoveRight], Duration::from_secs(5)).unwrap();
+    assert!(times <= 26, "{}", times);
+}
+
+#[test]
+fn panning_goes_by_time_not_frames() {
+    let pan_for = |frame: Duration, frames: u32| {
+        let mut camera = Camera::new(0, 0, 10000, 10000, 5);
+        let mut pan = Pan::new();
+        for _ in 0..frames {
+            pan.update(&mut camera, &[Action::CameraUp, Action::CameraRight], frame);
+        }
+        (camera.x_offset, camera.y_offset)
+    };
+    let second = PAN_SPEED as i32;
+    assert_eq!(pan_for(ms(10), 100), (-second, second));
+    assert_eq!(pan_for(ms(100), 10), (-second, second));
+    // Fast frames pan less than a pixel each, and those add up
+    assert_eq!(pan_for(Duration::from_micros(100), 10000), (-second, second));
+}
+
+#[test]
+fn panning_stops_at_the_edge() {
+    let mut camera = Camera::new(0, 0, 30, 20, 5);
+    let mut pan = Pan::new();
+    for _ in 0..20 {
+        pan.update(&mut camera, &[Action::CameraLeft, Action::CameraDown, Action::CameraDown], ms(100));
+    }
+    assert_eq!((camera.x_offset, camera.y_offset), (30, -20));
+    // Opposite keys cancel out
+    pan.update(&mut camera, &[Action::CameraLeft, Action::CameraRight], ms(100));
+    assert_eq!((camera.x_offset, camera.y_offset), (30, -20));
+}
@@ -0,0 +1,69 @@
+extern crate sokoban_rs;
+
+mod common;
+
+use std::env;
+use std::fs;
+
+use sokoban_rs::{Game, Position};
+use sokoban_rs::keys::{Action, Bindings, ACTIONS, DEFAULT_REPEAT_RATE};
+
+use common::{level, scratch_dir};
+
+#[test]
+fn every_action_has_its_own_default_key() {
+    let bindings = Bindings::defaults(env::temp_dir().join("unused"));
+    for &action in ACTIONS.iter() {
+        assert_eq!(Action::from_name(action.name()), Some(action));
+        for key in action.default_keys() {
+            assert_eq!(bindings.action(key), Some(action), "{}", key);
+        }
+    }
+    assert_eq!(bindings.action("up"), Some(Action::MoveUp));
+    assert_eq!(bindings.action("F12"), None);
+}
+
+#[test]
+fn binding_a_key_takes_it_from_other_actions() {
+    let mut bindings = Bindings::defaults(env::temp_dir().join("unused"));
+    bindings.bind(Action::Undo, "Z");
+    bindings.bind(Action::Undo, "Backspace");
+    assert_eq!(bindings.keys(Action::Undo), ["U", "Pad LB", "Z", "Backspace"]);
+    assert_eq!(bindings.keys(Action::Reset), ["Pad Back"]);
+    bindings.clear(Action::Undo);
+    assert_eq!(bindings.action("U"), None);
+    bindings.reset();
+    assert_eq!(bindings.action("Backspace"), Some(Action::Reset));
+}
+
+#[test]
+fn bindings_file_overrides_the_defaults() {
+    let dir = scratch_dir("keys");
+    let path = dir.join("keys.txt");
+    fs::write(&path, "# my keys\nmove up: K, Up\nQuit: ,\nNo such action: X\nUndo\nRepeat delay: 400\nRepeat rate: 0\n").unwrap();
+    let bindings = Bindings::load(path.clone());
+    assert_eq!(bindings.keys(Action::MoveUp), ["K", "Up"]);
+    assert_eq!(bindings.keys(Action::Quit), [","]);
+    assert_eq!(bindings.repeat_delay, 400);
+    // A rate that would never repeat keeps the default
+    assert_eq!(bindings.repeat_rate, DEFAULT_REPEAT_RATE);
+    // Actions the file leaves out keep their keys
+    assert_eq!(bindings.keys(Action::Undo), ["U", "Pad LB"]);
+
+    bindings.save().unwrap();
+    assert_eq!(Bindings::load(path), bindings);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn actions_move_the_pusher() {
+    let mut game = Game::from_level(level("######\n#@ $.#\n######"));
+    game.act(Action::MoveRight);
+    game.act(Action::MoveRight);
+    assert!(game.solved());
+    game.act(Action::Undo);
+    assert_eq!(game.state.player().position, Position::new(2, 1));
+    // Actions for the front end leave the game alone
+    game.act(Action::NextLevel);
+    assert_eq!(game.state.steps, 1);
+}
@@ -0,0 +1,55 @@
+//! Where things go on the minimap, for a level far wider than the screen.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::{Game, Position, HALF_WIDTH};
+use sokoban_rs::minimap::{Minimap, MARGIN, MAX_SIZE};
+
+use common::{corridor, level};
+
+#[test]
+fn the_minimap_keeps_the_level_shape_in_the_corner() {
+    let minimap = Minimap::new(&corridor());
+    let (left, top, w, h) = minimap.area();
+    // 2560 by 192 pixels of level, shrunk to fit the width
+    assert_eq!((w, h), (MAX_SIZE.0, 18));
+    assert_eq!((left + w as i32, top), (2 * HALF_WIDTH as i32 - MARGIN, MARGIN));
+    assert!(minimap.contains(left, top));
+    assert!(!minimap.contains(left + w as i32, top));
+    // Cells are at least a pixel across
+    assert_eq!(minimap.cell(Position::new(0, 0)), (left, top, 6, 6));
+}
+
+#[test]
+fn the_viewport_follows_the_camera() {
+    let mut game = corridor();
+    let minimap = Minimap::new(&game);
+    let (left, top, _, h) = minimap.area();
+    // The middle 900 pixels of the level, and all of it top to bottom
+    assert_eq!(minimap.viewport(&game.camera), (left + 77, top, 84, h));
+    game.camera.pan(10000, 0);
+    let (x, _, w, _) = minimap.viewport(&game.camera);
+    assert_eq!(x, left);
+    assert!(w < 84);
+
+    // A level smaller than the screen is all in view
+    let small = Game::from_level(level("#####\n#@$.#\n#####"));
+    let minimap = Minimap::new(&small);
+    assert_eq!(minimap.viewport(&small.camera), minimap.area());
+}
+
+#[test]
+fn clicking_the_minimap_looks_there() {
+    let mut game = corridor();
+    let minimap = Minimap::new(&game);
+    let (x, y, w, h) = minimap.cell(Position::new(20, 1));
+    minimap.look_at(&mut game.camera, x + w as i32 / 2, y + h as i32 / 2);
+    // The middle of cell 20 is 32 pixels right of the level's middle
+    assert_eq!((game.camera.x_offset, game.camera.y_offset), (-32, 0));
+    // Clicking the far end goes as far as the camera pans
+    let (left, top, w, _) = minimap.area();
+    minimap.look_at(&mut game.camera, left + w as i32 - 1, top);
+    assert_eq!(game.camera.x_offset, -game.camera.max_x_pan);
+}
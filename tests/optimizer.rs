@@ -0,0 +1,31 @@
+//! The optimizer holds solutions to the same rules as --verify.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::optimizer::{self, Metric, DEFAULT_VICINITY};
+use sokoban_rs::verifier;
+
+use common::level;
+
+fn optimize(text: &str, solution: &str) -> Result<optimizer::Solution, String> {
+    optimizer::optimize(&level(text), solution, Metric::Moves, DEFAULT_VICINITY, |_| true)
+}
+
+#[test]
+fn detours_are_cut_out() {
+    let text = "#######\n#     #\n#@ $ .#\n#######";
+    let shorter = optimize(text, "urdRR").unwrap();
+    assert_eq!((shorter.moves, shorter.pushes), (3, 2));
+    assert!(verifier::verify(&level(text), &shorter.lurd).solved);
+}
+
+#[test]
+fn pushes_have_to_be_written_as_pushes() {
+    let text = "#######\n#@ $ .#\n#######";
+    assert!(optimize(text, "rRR").is_ok());
+    // Both of these play out the same, but --verify rejects them
+    assert_eq!(optimize(text, "rrr").unwrap_err(), "Character 2 of the solution is not a legal move");
+    assert_eq!(optimize(text, "RRR").unwrap_err(), "Character 1 of the solution is not a legal move");
+}
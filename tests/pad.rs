@@ -0,0 +1,79 @@
+//! Synthetic gamepad input, the way SDL's game controller events report it.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use std::env;
+
+use sokoban_rs::{Game, Position};
+use sokoban_rs::keys::{Action, Bindings};
+use sokoban_rs::pad::{self, Sticks, STICK_PUSH, STICK_RELEASE};
+
+use common::level;
+
+#[test]
+fn buttons_have_binding_names() {
+    let bindings = Bindings::defaults(env::temp_dir().join("unused"));
+    let action = |button| pad::button_name(button).and_then(|name| bindings.action(name));
+    assert_eq!(action("dpup"), Some(Action::MoveUp));
+    assert_eq!(action("dpright"), Some(Action::MoveRight));
+    assert_eq!(action("leftshoulder"), Some(Action::Undo));
+    assert_eq!(action("rightshoulder"), Some(Action::Redo));
+    assert_eq!(action("start"), Some(Action::LevelBrowser));
+    assert_eq!(pad::button_name("touchpad"), None);
+}
+
+#[test]
+fn pushing_a_stick_presses_once() {
+    let mut sticks = Sticks::new();
+    assert_eq!(sticks.moved(0, "leftx", 1000), None);
+    assert_eq!(sticks.moved(0, "leftx", STICK_PUSH as i16), Some("Pad Stick Right"));
+    // Holding it over doesn't press again, even wobbling back a little
+    assert_eq!(sticks.moved(0, "leftx", 32767), None);
+    assert_eq!(sticks.moved(0, "leftx", STICK_RELEASE as i16 + 1), None);
+    assert_eq!(sticks.moved(0, "leftx", 32767), None);
+    // Letting it go back to the middle lets it press again
+    assert_eq!(sticks.moved(0, "leftx", 0), None);
+    assert_eq!(sticks.moved(0, "leftx", 20000), Some("Pad Stick Right"));
+    // Flicking it straight across presses the other way
+    assert_eq!(sticks.moved(0, "leftx", -32768), Some("Pad Stick Left"));
+}
+
+#[test]
+fn sticks_and_pads_are_kept_apart() {
+    let mut sticks = Sticks::new();
+    assert_eq!(sticks.moved(0, "lefty", -20000), Some("Pad Stick Up"));
+    assert_eq!(sticks.moved(1, "lefty", -20000), Some("Pad Stick Up"));
+    assert_eq!(sticks.moved(0, "righty", 20000), Some("Pad Stick Down"));
+    assert_eq!(sticks.moved(0, "lefttrigger", 32767), None);
+    sticks.forget(0);
+    assert_eq!(sticks.moved(0, "lefty", -20000), Some("Pad Stick Up"));
+    assert_eq!(sticks.moved(1, "lefty", -20000), None);
+}
+
+#[test]
+fn shoulder_buttons_undo_and_redo() {
+    let bindings = Bindings::defaults(env::temp_dir().join("unused"));
+    let mut game = Game::from_level(level("#######\n#@ $ .#\n#######"));
+    let mut sticks = Sticks::new();
+    for &(axis, value) in &[("leftx", 30000), ("leftx", 0), ("leftx", 30000)] {
+        if let Some(action) = sticks.moved(0, axis, value).and_then(|name| bindings.action(name)) {
+            game.act(action);
+        }
+    }
+    assert_eq!(game.state.stars[0].position, Position::new(4, 1));
+    let press = |game: &mut Game, button| game.act(bindings.action(pad::button_name(button).unwrap()).unwrap());
+    press(&mut game, "leftshoulder");
+    press(&mut game, "leftshoulder");
+    assert_eq!(game.state.player().position, Position::new(1, 1));
+    press(&mut game, "rightshoulder");
+    assert_eq!(game.state.player().position, Position::new(2, 1));
+    assert_eq!(game.state.stars[0].position, Position::new(3, 1));
+    // A new move drops what could be redone
+    press(&mut game, "dpdown");
+    press(&mut game, "dpright");
+    press(&mut game, "rightshoulder");
+    assert_eq!(game.state.player().position, Position::new(3, 1));
+    assert!(game.undone.is_empty());
+}
@@ -0,0 +1,148 @@
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::{Direction, Game, Position, Tile, Topology, load_levels, metadata_value};
+
+use common::{LEVEL_FILES, all_levels, level};
+
+#[test]
+fn player_on_goal_is_a_goal() {
+    let level = level("#####\n#+$.#\n#####");
+    let goals = level.start_state.goals.iter().map(|g| g.position).collect::<Vec<_>>();
+    assert_eq!(goals, vec![Position::new(1, 1), Position::new(3, 1)]);
+    assert_eq!(level.start_state.player().position, Position::new(1, 1));
+}
+
+#[test]
+fn star_on_goal_is_a_goal() {
+    let level = level("######\n#@ *.#\n# $  #\n######");
+    let goals = level.start_state.goals.iter().map(|g| g.position).collect::<Vec<_>>();
+    assert_eq!(goals, vec![Position::new(3, 1), Position::new(4, 1)]);
+    assert_eq!(level.start_state.stars.len(), 2);
+}
+
+#[test]
+fn last_level_needs_no_blank_line() {
+    let levels = load_levels("#####\n#@$.#\n#####\n\n#####\n#.$@#\n#####").unwrap();
+    assert_eq!(levels.len(), 2);
+    assert_eq!(levels[1].start_state.player().position, Position::new(3, 1));
+}
+
+#[test]
+fn comments_and_metadata_are_split_off() {
+    let level = level("; A comment\nTitle: Example\n#####\n#@$.# ; trailing comment\n#####\n");
+    assert_eq!(level.metadata, vec![("Title".to_string(), "Example".to_string())]);
+    assert_eq!(level.width, 5);
+    assert_eq!(level.height, 3);
+}
+
+#[test]
+fn solutions_can_go_over_several_lines() {
+    let text = "Title: Long way round\n######\n#@   #\n# $  #\n#   .#\n######\nSolution (moves 6, pushes 3):\ndRR\nurD\n\n#####\n#@$.#\n#####\nSolution\nR\n";
+    let levels = load_levels(text).unwrap();
+    assert_eq!(levels.len(), 2);
+    assert_eq!(metadata_value(&levels[0].metadata, "Solution"), Some("dRRurD"));
+    assert_eq!(metadata_value(&levels[0].metadata, "Title"), Some("Long way round"));
+    assert_eq!(levels[0].par(), Some((6, 3)));
+    assert_eq!(metadata_value(&levels[1].metadata, "Solution"), Some("R"));
+}
+
+#[test]
+fn bad_levels_are_rejected() {
+    assert!(load_levels("#####\n# $.#\n#####").is_err(), "no starting position");
+    assert!(load_levels("#####\n#@$X#\n#####").is_err(), "unknown tile");
+    assert!(load_levels("Topology: Cube\n#####\n#@$.#\n#####").is_err(), "unknown topology");
+    assert!(load_levels("Star colors: 1 2\n#####\n#@$.#\n#####").is_err(), "too many colors");
+    assert!(load_levels("#####\n#@$1#\n#####").is_err(), "teleporter without a partner");
+}
+
+#[test]
+fn walls_are_found_across_the_whole_width() {
+    // Wider than it is high, so walls past the height used to be missed
+    let level = level("##########\n#@ $    .#\n##########");
+    for x in 0..10 {
+        assert!(level.is_wall(x, 0));
+        assert!(level.is_wall(x, 2));
+    }
+    assert!(level.is_wall(9, 1));
+    assert!(!level.is_wall(8, 1));
+    assert!(!level.is_wall(10, 1));
+    assert_eq!(level.tile(10, 1), None);
+}
+
+#[test]
+fn inside_floor_is_filled_from_the_player() {
+    let level = level(" #####\n #@$.#\n #####\n#  #");
+    assert_eq!(level.map[1][2], Tile::InsideFloor);
+    assert_eq!(level.map[0][0], Tile::OutsideFloor);
+    assert_eq!(level.map[3][1], Tile::OutsideFloor);
+}
+
+#[test]
+fn bundled_levels_round_trip() {
+    for &(name, text) in &LEVEL_FILES {
+        for (i, level) in load_levels(text).unwrap().into_iter().enumerate() {
+            let again = load_levels(&level.text()).unwrap_or_else(|e| panic!("{} level {}: {}", name, i + 1, e));
+            assert_eq!(again, vec![level], "{} level {} changed when written out and read back", name, i + 1);
+        }
+    }
+}
+
+#[test]
+fn map_lines_show_the_start() {
+    let text = "####\n# .###\n#*@$ #\n#   .#\n######";
+    let level = level(text);
+    assert_eq!(level.map_lines(&level.start_state).join("\n"), text);
+}
+
+#[test]
+fn copied_positions_load_as_levels() {
+    let mut game = Game::from_level(level("#######\n#@ $ .#\n#  $ .#\n#######"));
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Right);
+    let copied = game.level.position_text(&game.state);
+    assert_eq!(copied, game.level.map_lines(&game.state).join("\n"));
+    // Pasted text often has Windows line endings
+    let pasted = load_levels(&copied.replace('\n', "\r\n")).unwrap();
+    assert_eq!(pasted.len(), 1);
+    let stars = pasted[0].start_state.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+    assert_eq!(stars, vec![Position::new(4, 1), Position::new(3, 2)]);
+    assert_eq!(pasted[0].start_state.player().position, Position::new(3, 1));
+}
+
+#[test]
+fn copied_hexoban_positions_stay_hexoban() {
+    let mut game = Game::from_level(level("Topology: Hexoban\n   # # # # #\n  #   .   . #\n #   $ @ $   #\n  #         #\n   # # # # #"));
+    game.make_move(Direction::Right);
+    let pasted = level(&game.level.position_text(&game.state));
+    assert_eq!(pasted.topology, Topology::Hex);
+    let stars = pasted.start_state.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+    assert_eq!(stars, vec![Position::new(5, 2), Position::new(11, 2)]);
+    assert_eq!(pasted.start_state.player().position, Position::new(9, 2));
+}
+
+#[test]
+fn copied_positions_keep_their_colors() {
+    let mut game = Game::from_level(level("Star colors: 1 2\nGoal colors: 2 1\n#######\n#.   .#\n#     #\n# $@$ #\n#     #\n#######"));
+    // Push the second star above the first, so the stars come in the other order
+    game.make_move(Direction::Down);
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Up);
+    let pasted = level(&game.level.position_text(&game.state));
+    let stars = pasted.start_state.stars.iter().map(|s| (s.position, s.color)).collect::<Vec<_>>();
+    assert_eq!(stars, vec![(Position::new(4, 2), Some(2)), (Position::new(2, 3), Some(1))]);
+    let goals = pasted.start_state.goals.iter().map(|g| (g.position, g.color)).collect::<Vec<_>>();
+    assert_eq!(goals, vec![(Position::new(1, 1), Some(2)), (Position::new(5, 1), Some(1))]);
+}
+
+#[test]
+fn every_bundled_level_has_a_goal_for_every_star() {
+    for (i, level) in all_levels().iter().enumerate() {
+        let state = &level.start_state;
+        // Holes can swallow spare stars, so those levels may have more stars than goals
+        if !level.has_holes() {
+            assert_eq!(state.stars.len(), state.goals.len(), "level {}", i + 1);
+        }
+    }
+}
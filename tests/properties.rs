@@ -0,0 +1,148 @@
+//! Random move sequences on the bundled levels, checking that the rules never break the board.
+
+extern crate sokoban_rs;
+extern crate proptest;
+
+mod common;
+
+use proptest::prelude::*;
+
+use sokoban_rs::{Game, Level, Tile, Topology};
+use sokoban_rs::transform::Transform;
+
+use common::all_levels;
+
+thread_local! {
+    static LEVELS: Vec<Level> = all_levels();
+}
+
+fn level(i: usize) -> Level {
+    LEVELS.with(|levels| levels[i % levels.len()].clone())
+}
+
+/// Checks everything that has to hold in any position reached by playing.
+fn check_invariants(game: &Game, stars: usize) {
+    let level = &game.level;
+    let state = &game.state;
+    let on_map = |x: usize, y: usize| x < level.width && y < level.height;
+    // Stars only leave the board by filling holes
+    assert_eq!(state.stars.len() + state.filled_holes.len(), stars);
+    for (i, player) in state.players.iter().enumerate() {
+        let pos = player.position;
+        assert!(on_map(pos.x, pos.y), "pusher {} left the map at {:?}", i, pos);
+        assert_ne!(level.map[pos.y][pos.x], Tile::Wall, "pusher {} is inside a wall", i);
+        assert!(level.map[pos.y][pos.x] != Tile::Hole || state.filled_holes.contains(&pos), "pusher {} fell into a hole", i);
+        assert!(state.players[i + 1..].iter().all(|p| p.position != pos), "two pushers share {:?}", pos);
+        assert!(state.stars.iter().all(|s| s.position != pos), "pusher {} shares {:?} with a star", i, pos);
+    }
+    for (i, star) in state.stars.iter().enumerate() {
+        let pos = star.position;
+        assert!(on_map(pos.x, pos.y), "star {} left the map at {:?}", i, pos);
+        assert_ne!(level.map[pos.y][pos.x], Tile::Wall, "star {} is inside a wall", i);
+        assert!(state.stars[i + 1..].iter().all(|s| s.position != pos), "two stars share {:?}", pos);
+    }
+    assert_eq!(game.history.len(), state.steps);
+    assert_eq!(game.history.iter().filter(|m| m.push).count(), state.pushes);
+}
+
+proptest! {
+    #[test]
+    fn random_moves_keep_the_board_sound(i in any::<usize>(), moves in prop::collection::vec((0..8usize, any::<bool>()), 0..200)) {
+        let mut game = Game::from_level(level(i));
+        let stars = game.state.stars.len();
+        for (dir, switch) in moves {
+            let directions = game.level.topology.directions();
+            if switch && dir == 0 {
+                game.state.switch_player();
+            }
+            game.make_move(directions[dir % directions.len()]);
+            check_invariants(&game, stars);
+        }
+    }
+
+    #[test]
+    fn reachable_squares_can_be_walked_to(i in any::<usize>(), moves in prop::collection::vec(0..8usize, 0..50), pick in any::<usize>()) {
+        let mut game = Game::from_level(level(i));
+        for dir in moves {
+            let directions = game.level.topology.directions();
+            game.make_move(directions[dir % directions.len()]);
+        }
+        let squares = game.reachability().positions();
+        prop_assume!(!squares.is_empty());
+        let target = squares[pick % squares.len()];
+        let pushes = game.state.pushes;
+        game.walk_to(target);
+        prop_assert_eq!(game.state.player().position, target);
+        prop_assert_eq!(game.state.pushes, pushes);
+    }
+
+    #[test]
+    fn undo_takes_back_the_last_move(i in any::<usize>(), moves in prop::collection::vec((0..8usize, any::<bool>()), 1..100)) {
+        let mut game = Game::from_level(level(i));
+        let mut before = game.state.clone();
+        for (dir, switch) in moves {
+            let directions = game.level.topology.directions();
+            if switch && dir == 0 {
+                game.state.switch_player();
+            }
+            let state = game.state.clone();
+            game.make_move(directions[dir % directions.len()]);
+            if game.state.steps != state.steps {
+                before = state;
+            }
+        }
+        prop_assume!(!game.history.is_empty());
+        let active = game.state.active;
+        prop_assert!(game.undo());
+        let mut expected = before.clone();
+        expected.active = active;
+        // The pushers that didn't move may face another way, since replaying turns each pusher as it moves
+        let positions = |s: &sokoban_rs::GameState| s.players.iter().map(|p| p.position).collect::<Vec<_>>();
+        prop_assert_eq!(positions(&game.state), positions(&expected));
+        prop_assert_eq!(&game.state.stars, &expected.stars);
+        prop_assert_eq!(&game.state.filled_holes, &expected.filled_holes);
+        prop_assert_eq!((game.state.steps, game.state.pushes), (expected.steps, expected.pushes));
+    }
+
+    #[test]
+    fn redo_puts_back_what_undo_took(i in any::<usize>(), moves in prop::collection::vec(0..8usize, 1..60), undos in 1..10usize) {
+        let mut game = Game::from_level(level(i));
+        for dir in moves {
+            let directions = game.level.topology.directions();
+            game.make_move(directions[dir % directions.len()]);
+        }
+        let played = game.clone();
+        let undone = (0..undos).take_while(|_| game.undo()).count();
+        prop_assert_eq!(game.undone.len(), undone);
+        for _ in 0..undone {
+            prop_assert!(game.redo());
+        }
+        prop_assert!(!game.redo());
+        prop_assert_eq!(&game.history, &played.history);
+        prop_assert_eq!(&game.state.stars, &played.state.stars);
+        prop_assert_eq!(game.state.player().position, played.state.player().position);
+    }
+
+    #[test]
+    fn turned_levels_play_the_same(i in any::<usize>(), t in 0..8usize, moves in prop::collection::vec(0..4usize, 0..100)) {
+        let level = level(i);
+        prop_assume!(level.topology == Topology::Square);
+        let t = Transform::all()[t];
+        let (w, h) = (level.width, level.height);
+        let mut game = Game::from_level(level.clone());
+        let mut turned = Game::from_level(level).transformed(t);
+        for dir in moves {
+            let dir = Topology::Square.directions()[dir];
+            game.make_move(dir);
+            turned.make_move(t.direction(dir));
+        }
+        let expected = t.state(&game.state, w, h);
+        let mut expected_stars = expected.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+        let mut stars = turned.state.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+        expected_stars.sort();
+        stars.sort();
+        prop_assert_eq!(turned.state.player().position, expected.player().position);
+        prop_assert_eq!(stars, expected_stars);
+        prop_assert_eq!((turned.state.steps, turned.state.pushes), (game.state.steps, game.state.pushes));
+    }
+}
@@ -0,0 +1,38 @@
+//! The file that keeps the best solution for every level.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use std::fs;
+
+use sokoban_rs::save::{Record, SaveData};
+
+use common::scratch_dir;
+
+#[test]
+fn records_load_back() {
+    let path = scratch_dir("save").join("solutions.txt");
+    let mut data = SaveData::load(path.clone());
+    assert!(data.record(1, Record { moves: 3, pushes: 2, solution: "rRR".to_string() }));
+    // A level that starts solved has nothing to write for its solution
+    assert!(data.record(2, Record { moves: 0, pushes: 0, solution: String::new() }));
+    data.save().unwrap();
+    assert_eq!(SaveData::load(path), data);
+}
+
+#[test]
+fn only_better_records_are_kept() {
+    let mut data = SaveData::load(scratch_dir("save-better").join("solutions.txt"));
+    assert!(data.record(1, Record { moves: 5, pushes: 2, solution: "urdRR".to_string() }));
+    assert!(!data.record(1, Record { moves: 5, pushes: 3, solution: "urdRR".to_string() }));
+    assert!(data.record(1, Record { moves: 3, pushes: 2, solution: "rRR".to_string() }));
+    assert_eq!(data.best(1).unwrap().solution, "rRR");
+}
+
+#[test]
+fn empty_solutions_from_older_files_are_kept() {
+    let path = scratch_dir("save-older").join("solutions.txt");
+    fs::write(&path, "0000000000000002 0 0 \n").unwrap();
+    assert_eq!(SaveData::load(path).best(2), Some(&Record { moves: 0, pushes: 0, solution: String::new() }));
+}
@@ -0,0 +1,84 @@
+//! The compact positions searches use, and the solver that rates levels.
+
+extern crate sokoban_rs;
+
+mod common;
+
+use sokoban_rs::{Direction, Game, Position};
+use sokoban_rs::bitboard::{Bitset, Board};
+use sokoban_rs::difficulty;
+use sokoban_rs::reachability::Reachability;
+
+use common::level;
+
+#[test]
+fn bitsets_list_their_squares_in_order() {
+    let mut set = Bitset::new(200);
+    for &square in &[130, 0, 64, 63, 199] {
+        set.insert(square);
+    }
+    assert_eq!(set.iter().collect::<Vec<_>>(), vec![0, 63, 64, 130, 199]);
+    assert_eq!(Bitset::new(200).iter().next(), None);
+}
+
+#[test]
+fn compact_states_expand_back() {
+    let level = level("########\n#@ $ . #\n# *  $.#\n########");
+    let board = Board::new(&level);
+    let mut game = Game::from_level(level.clone());
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Right);
+    let compact = board.compact(&game.state);
+    let expanded = board.expand(&compact);
+    assert_eq!(expanded.player().position, game.state.player().position);
+    let mut stars = expanded.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+    let mut expected = game.state.stars.iter().map(|s| s.position).collect::<Vec<_>>();
+    stars.sort_by_key(|p| (p.y, p.x));
+    expected.sort_by_key(|p| (p.y, p.x));
+    assert_eq!(stars, expected);
+    assert_eq!(expanded.goals, game.state.goals);
+    assert_eq!(board.compact(&expanded), compact);
+    assert_eq!(board.position(board.square(Position::new(5, 2))), Position::new(5, 2));
+}
+
+#[test]
+fn normalizing_forgets_where_the_pusher_stands() {
+    let level = level("#######\n#@    #\n# $  .#\n#######");
+    let board = Board::new(&level);
+    let mut game = Game::from_level(level.clone());
+    let before = board.normalized(&game.state, &Reachability::new(&level, &game.state));
+    game.make_move(Direction::Right);
+    game.make_move(Direction::Right);
+    let after = board.normalized(&game.state, &Reachability::new(&level, &game.state));
+    assert_eq!(before, after);
+    assert_ne!(board.compact(&Game::from_level(level).state), board.compact(&game.state));
+}
+
+#[test]
+fn squares_off_an_open_edge_arent_on_the_board() {
+    let level = level("@$ .");
+    let board = Board::new(&level);
+    let compact = board.compact(&level.start_state);
+    let left = Position::new(0, 0).move_in_direction(level.topology, Direction::Up);
+    assert!(!board.contains(left));
+    assert!(!board.is_star(&compact, left));
+    assert!(board.is_star(&compact, Position::new(1, 0)));
+}
+
+#[test]
+fn open_levels_can_be_rated() {
+    let level = level("@$ .");
+    assert_eq!(difficulty::solve(&level).1, Some((2, 2)));
+    assert_eq!(difficulty::rate(&level).solution, Some((2, 2)));
+}
+
+#[test]
+fn the_solver_counts_moves_and_pushes() {
+    assert_eq!(difficulty::solve(&level("#####\n#@$.#\n#####")).1, Some((1, 1)));
+    // Three steps over and one down before three pushes, counted from where the pusher really starts
+    assert_eq!(difficulty::solve(&level("#######\n#   @ #\n# $  .#\n#######")).1, Some((7, 3)));
+    // Pushed one way, then walked around to push the other
+    assert_eq!(difficulty::solve(&level("######\n#@   #\n# $  #\n#   .#\n######")).1, Some((6, 3)));
+    // A star against the top wall can never get down to the goal
+    assert_eq!(difficulty::solve(&level("#####\n#@$ #\n#  .#\n#####")).1, None);
+}
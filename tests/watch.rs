@@ -0,0 +1,45 @@
+extern crate sokoban_rs;
+
+mod common;
+
+use std::fs::{self, File};
+use std::io::Write;
+
+use sokoban_rs::watch::FileWatch;
+
+use common::scratch_dir;
+
+#[test]
+fn saving_the_file_is_noticed_once() {
+    let dir = scratch_dir("watch");
+    let path = dir.join("levels.txt");
+    fs::write(&path, "#####\n#@$.#\n#####\n").unwrap();
+    let mut watch = FileWatch::new(path.clone());
+    assert!(!watch.check());
+
+    let mut f = File::create(&path).unwrap();
+    write!(f, "######\n#@$ .#\n######\n").unwrap();
+    drop(f);
+    assert!(watch.check());
+    assert!(!watch.check());
+
+    // An editor replacing the file leaves it missing for a moment
+    fs::remove_file(&path).unwrap();
+    assert!(!watch.check());
+    fs::write(&path, "#####\n#@$.#\n#####\n").unwrap();
+    assert!(watch.check());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn polling_waits_between_looks() {
+    let dir = scratch_dir("poll");
+    let path = dir.join("levels.txt");
+    fs::write(&path, "#####\n#@$.#\n#####\n").unwrap();
+    let mut watch = FileWatch::new(path.clone());
+    fs::write(&path, "######\n#@$ .#\n######\n").unwrap();
+    // It was only just made, so it hasn't looked again yet
+    assert!(!watch.poll());
+    assert!(watch.check());
+    fs::remove_dir_all(&dir).unwrap();
+}